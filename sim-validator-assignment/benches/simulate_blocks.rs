@@ -0,0 +1,147 @@
+use clap::Parser;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use sim_validator_assignment::compact::CompactPopulation;
+use sim_validator_assignment::config::Config;
+use sim_validator_assignment::partial_seat::ShuffledPartialSeats;
+use sim_validator_assignment::rng::{RngBackend, Shuffle, SimRng};
+use sim_validator_assignment::run::simulate_blocks;
+use sim_validator_assignment::seat::ShuffledSeats;
+use sim_validator_assignment::shard::Shard;
+use sim_validator_assignment::validator::{
+    new_ordered_partial_seats, new_ordered_seats, parse_raw_validator_data, RawValidatorData,
+    Validator,
+};
+
+/// The number of blocks simulated per benchmark iteration.
+const NUM_BLOCKS: u64 = 100;
+
+/// Allows constructing a [`Config`] from CLI arguments, which keeps benchmarks independent of
+/// parameters they do not care about.
+#[derive(Parser)]
+struct BenchCli {
+    #[command(flatten)]
+    config: Config,
+}
+
+/// Returns the parameters used in the README to simulate NEAR mainnet.
+fn new_mainnet_config() -> Config {
+    BenchCli::parse_from([
+        "bench",
+        "--num-blocks",
+        "100",
+        "--num-shards",
+        "6",
+        "--seats-per-shard",
+        "68",
+        "--stake-per-seat",
+        "100",
+        "--max-malicious-stake-per-shard",
+        "2/3",
+        "--include-partial-seats",
+    ])
+    .config
+}
+
+/// Returns a set of validators similar in size to NEAR mainnet. Stakes vary between validators
+/// and most of them hold a partial seat.
+fn new_mainnet_validators() -> Vec<RawValidatorData> {
+    (0..300)
+        .map(|i| RawValidatorData {
+            account_id: format!("validator_{i}"),
+            stake: 100 * (1 + i % 4) + 37 * (i % 3),
+            is_malicious: i % 4 == 0,
+        })
+        .collect()
+}
+
+/// Simulates blocks with seats referencing validators, allocating seats and shard assignments for
+/// every block. Serves as baseline for the benchmark.
+fn simulate_blocks_allocating(
+    config: &Config,
+    validators: &[Validator],
+    rng: &mut impl Shuffle,
+    num_blocks: u64,
+) -> u64 {
+    let mut num_corrupted_shards = 0;
+    for _ in 0..num_blocks {
+        let mut seats = new_ordered_seats(validators);
+        rng.shuffle(&mut seats);
+        let mut partial_seats = new_ordered_partial_seats(validators, config.stake_per_seat.get());
+        rng.shuffle(&mut partial_seats);
+
+        for shard_idx in 0..usize::from(config.num_shards) {
+            let shard_seats = config
+                .collect_seats_for_shard(shard_idx, &seats)
+                .unwrap()
+                .to_vec();
+            let shard_partial_seats: Vec<_> = config
+                .collect_partial_seats_for_shard(shard_idx, &partial_seats)
+                .unwrap()
+                .collect();
+            let shard = Shard::new(config, &shard_seats, shard_partial_seats).unwrap();
+            if shard.is_corrupted(config.max_malicious_stake_per_shard[0]) {
+                num_corrupted_shards += 1;
+            }
+        }
+    }
+    num_corrupted_shards
+}
+
+/// Simulates blocks with seats referencing validators, reusing seat buffers across blocks.
+fn simulate_blocks_reusing_buffers(
+    config: &Config,
+    validators: &[Validator],
+    rng: &mut impl Shuffle,
+    num_blocks: u64,
+) -> u64 {
+    let mut seats = new_ordered_seats(validators);
+    let mut partial_seats = new_ordered_partial_seats(validators, config.stake_per_seat.get());
+    let mut num_corrupted_shards = 0;
+    for _ in 0..num_blocks {
+        let shuffled_seats = ShuffledSeats::new(&mut seats, rng);
+        let shuffled_partial_seats = ShuffledPartialSeats::new(&mut partial_seats, rng);
+
+        for shard_idx in 0..usize::from(config.num_shards) {
+            let shard_seats = config
+                .collect_seats_for_shard(shard_idx, shuffled_seats.get_seats())
+                .unwrap();
+            let shard_partial_seats = config
+                .collect_partial_seats_for_shard(
+                    shard_idx,
+                    shuffled_partial_seats.get_partial_seats(),
+                )
+                .unwrap();
+            let shard = Shard::new(config, shard_seats, shard_partial_seats).unwrap();
+            if shard.is_corrupted(config.max_malicious_stake_per_shard[0]) {
+                num_corrupted_shards += 1;
+            }
+        }
+    }
+    num_corrupted_shards
+}
+
+fn bench_simulate_blocks(c: &mut Criterion) {
+    let config = new_mainnet_config();
+    let (_, validators) =
+        parse_raw_validator_data(&new_mainnet_validators(), config.stake_per_seat.get()).unwrap();
+
+    let mut group = c.benchmark_group("simulate_blocks");
+    group.throughput(Throughput::Elements(NUM_BLOCKS));
+    group.bench_function(BenchmarkId::new("allocating", NUM_BLOCKS), |b| {
+        let mut rng = SimRng::new(RngBackend::Fastrand, 0, 0, None).unwrap();
+        b.iter(|| simulate_blocks_allocating(&config, &validators, &mut rng, NUM_BLOCKS))
+    });
+    group.bench_function(BenchmarkId::new("reusing_buffers", NUM_BLOCKS), |b| {
+        let mut rng = SimRng::new(RngBackend::Fastrand, 0, 0, None).unwrap();
+        b.iter(|| simulate_blocks_reusing_buffers(&config, &validators, &mut rng, NUM_BLOCKS))
+    });
+    let population = CompactPopulation::new(&validators, config.stake_per_seat.get()).unwrap();
+    group.bench_function(BenchmarkId::new("compact", NUM_BLOCKS), |b| {
+        let mut rng = SimRng::new(RngBackend::Fastrand, 0, 0, None).unwrap();
+        b.iter(|| simulate_blocks(&config, &population, &mut rng, NUM_BLOCKS).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_simulate_blocks);
+criterion_main!(benches);
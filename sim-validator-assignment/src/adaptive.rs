@@ -0,0 +1,496 @@
+use clap::Args;
+use num_rational::Ratio;
+use serde::Serialize;
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::compact::CompactPopulation;
+use crate::config::Config;
+use crate::output::{print_table, TableRow};
+use crate::rng::{Shuffle, SimRng};
+use crate::run::{
+    log_seed, mock_validator_data, prepare_population, seed_or_random, simulate_batch,
+};
+use crate::seat_price::StakePerSeat;
+use crate::shard::CorruptionMetric;
+use crate::sweep::SimulationArgs;
+use crate::validator::read_validator_data;
+
+#[derive(Args, Debug)]
+pub struct AdaptiveConfig {
+    /// The number of assignments of seats to shards to simulate.
+    #[arg(long)]
+    pub num_blocks: u64,
+    #[arg(long)]
+    pub num_shards: u16,
+    #[arg(long)]
+    pub seats_per_shard: u64,
+    /// The amount of stake required to get one seat. Pass `auto` to compute it from validator data
+    /// such that validators cover `num_shards * seats_per_shard` seats.
+    #[arg(long)]
+    pub stake_per_seat: StakePerSeat,
+    /// If the ratio of malicious stake is higher than this threshold, the shard is considered
+    /// corrupted.
+    #[arg(long)]
+    pub max_malicious_stake_per_shard: Ratio<u128>,
+    /// The amount the adversary may corrupt after seats are assigned, either `stake:<amount>` or
+    /// `validators:<n>`. Corrupting a validator costs its entire stake.
+    #[arg(long)]
+    pub corruption_budget: CorruptionBudget,
+    /// The number of blocks it takes the adversary to corrupt one validator.
+    #[arg(long, default_value_t = 1)]
+    pub corruption_delay: u64,
+    /// The file from which validator data is read. It is expected to contain a vector of
+    /// `RawValidatorData` serialized as JSON. If no validator data is provided, mocked validator
+    /// data will be used. Validators marked as malicious are corrupted from the start.
+    #[arg(long)]
+    pub validator_data: Option<PathBuf>,
+    #[command(flatten)]
+    pub args: SimulationArgs,
+}
+
+/// What an adaptive adversary may corrupt after seats are assigned to shards.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CorruptionBudget {
+    /// The total stake of validators the adversary may corrupt.
+    Stake(u128),
+    /// The number of validators the adversary may corrupt.
+    Validators(u64),
+}
+
+impl FromStr for CorruptionBudget {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("stake", amount)) => Ok(Self::Stake(amount.parse()?)),
+            Some(("validators", n)) => Ok(Self::Validators(n.parse()?)),
+            _ => anyhow::bail!("expected `stake:<amount>` or `validators:<n>`"),
+        }
+    }
+}
+
+impl Display for CorruptionBudget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Stake(amount) => write!(f, "stake:{amount}"),
+            Self::Validators(n) => write!(f, "validators:{n}"),
+        }
+    }
+}
+
+/// Counts the number of corruptions an adaptive adversary requires per assignment of seats.
+#[derive(PartialEq, Debug, Default)]
+pub struct CorruptionCounts {
+    pub num_blocks: u64,
+    /// The `k`th element holds the number of assignments in which the adversary corrupts a shard
+    /// by corrupting `k` validators.
+    pub num_corruptions: Vec<u64>,
+    /// The number of assignments in which the budget does not suffice to corrupt any shard.
+    pub num_infeasible: u64,
+}
+
+impl CorruptionCounts {
+    pub fn add(&mut self, num_corruptions: Option<u64>) {
+        self.num_blocks += 1;
+        match num_corruptions {
+            Some(k) => {
+                let k = usize::try_from(k).expect("number of corruptions should fit usize");
+                if self.num_corruptions.len() <= k {
+                    self.num_corruptions.resize(k + 1, 0);
+                }
+                self.num_corruptions[k] += 1;
+            }
+            None => self.num_infeasible += 1,
+        }
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        self.num_blocks += other.num_blocks;
+        if self.num_corruptions.len() < other.num_corruptions.len() {
+            self.num_corruptions.resize(other.num_corruptions.len(), 0);
+        }
+        for (count, other_count) in self
+            .num_corruptions
+            .iter_mut()
+            .zip(other.num_corruptions.iter())
+        {
+            *count += other_count;
+        }
+        self.num_infeasible += other.num_infeasible;
+    }
+}
+
+/// A row of the distribution of the time to corruption.
+#[derive(Serialize, Debug)]
+pub struct TimeToCorruptionRow {
+    pub num_corruptions: u64,
+    /// The number of blocks until a shard is corrupted, i.e. `num_corruptions * corruption_delay`.
+    pub time: u64,
+    pub count: u64,
+    pub probability: f64,
+    /// The probability that a shard is corrupted within `time` blocks.
+    pub cumulative_probability: f64,
+}
+
+impl TableRow for TimeToCorruptionRow {
+    fn header() -> String {
+        "num_corruptions\ttime\tcount\tp(time to corruption = time)\tp(time to corruption <= time)"
+            .to_owned()
+    }
+
+    fn cells(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{:.4e}\t{:.4e}",
+            self.num_corruptions,
+            self.time,
+            self.count,
+            self.probability,
+            self.cumulative_probability
+        )
+    }
+}
+
+/// Simulates an adaptive adversary which, after seats are assigned to shards, corrupts validators
+/// of the shard it can take over with the fewest corruptions. Each corruption takes
+/// `corruption_delay` blocks, so the distribution of the time to corruption indicates how
+/// frequently seats must be reshuffled.
+pub fn adaptive(config: &AdaptiveConfig) -> anyhow::Result<()> {
+    let raw_validator_data = match &config.validator_data {
+        Some(file_path) => read_validator_data(file_path.as_path())?,
+        None => mock_validator_data(),
+    };
+    let run_config = config.args.run_config(
+        config.num_blocks,
+        config.num_shards,
+        config.seats_per_shard,
+        config.stake_per_seat,
+        vec![config.max_malicious_stake_per_shard],
+    );
+    let (run_config, _, population) = prepare_population(&run_config, &raw_validator_data)?;
+    let stakes: Vec<u128> = raw_validator_data.iter().map(|v| v.stake).collect();
+
+    let seed = seed_or_random(config.args.seed);
+    let mut rngs = (0..config.args.threads)
+        .map(|stream| SimRng::new(run_config.rng, seed, stream.into(), None))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let mut counts = CorruptionCounts::default();
+    for worker_counts in simulate_batch(&mut rngs, config.num_blocks, |rng, num_blocks| {
+        simulate_adaptive_adversary(
+            &run_config,
+            &population,
+            &stakes,
+            config.corruption_budget,
+            rng,
+            num_blocks,
+        )
+    })? {
+        counts.merge(&worker_counts);
+    }
+
+    let rows = time_to_corruption(&counts, config.corruption_delay)?;
+    print_table(&rows);
+    println!(
+        "In {} of {} assignments the budget does not suffice to corrupt any shard.",
+        counts.num_infeasible, counts.num_blocks
+    );
+    println!("If seats are reshuffled every R blocks, the adversary corrupts a shard before the reshuffle with probability p(time to corruption <= R). For a budget of stake the time to corruption is an upper bound, hence the probability is a lower bound.");
+    config.args.write_table(&rows)?;
+    log_seed(seed, config.args.threads);
+    Ok(())
+}
+
+/// Simulates `num_blocks` assignments of seats and counts the corruptions the adversary requires
+/// for each of them.
+pub fn simulate_adaptive_adversary(
+    config: &Config,
+    population: &CompactPopulation,
+    stakes: &[u128],
+    budget: CorruptionBudget,
+    rng: &mut impl Shuffle,
+    num_blocks: u64,
+) -> anyhow::Result<CorruptionCounts> {
+    let mut seats = population.new_ordered_seats();
+    let mut partial_seats = if config.include_partial_seats {
+        population.new_ordered_partial_seats()
+    } else {
+        Vec::new()
+    };
+    let mut weights = Vec::new();
+    let mut counts = CorruptionCounts::default();
+
+    for _ in 0..num_blocks {
+        rng.shuffle(&mut seats);
+        rng.shuffle(&mut partial_seats);
+
+        let mut min_corruptions = None;
+        for shard_idx in 0..usize::from(config.num_shards) {
+            let shard_seats = config.collect_seats_for_shard(shard_idx, &seats)?;
+            let shard_partial_seats =
+                config.collect_partial_seats_for_shard(shard_idx, &partial_seats)?;
+            let num_corruptions = corruptions_to_corrupt_shard(
+                config,
+                population,
+                stakes,
+                budget,
+                shard_seats,
+                shard_partial_seats,
+                &mut weights,
+            );
+            if let Some(k) = num_corruptions {
+                min_corruptions = Some(min_corruptions.map_or(k, |min: u64| min.min(k)));
+            }
+        }
+        counts.add(min_corruptions);
+    }
+
+    Ok(counts)
+}
+
+/// Returns the number of honest validators the adversary must corrupt to make the shard's
+/// malicious ratio exceed the threshold, or `None` if `budget` does not suffice.
+///
+/// For a budget of validators, corrupting validators in the order of their weight in the shard, as
+/// measured by `config.corruption_metric`, minimizes the number of corruptions. For a budget of
+/// stake, choosing validators is a knapsack problem: the heaviest validators may exceed the budget
+/// while more validators with less stake do not. Validators are then additionally corrupted in the
+/// order of their weight per unit of stake and the smaller number is returned. It is an upper
+/// bound of the minimum, i.e. the adversary may need fewer corruptions than returned and `None`
+/// may be returned although the budget suffices.
+///
+/// `weights` is a buffer reused across calls to avoid allocations in the hot loop.
+fn corruptions_to_corrupt_shard<'seats>(
+    config: &Config,
+    population: &CompactPopulation,
+    stakes: &[u128],
+    budget: CorruptionBudget,
+    seats: &'seats [u32],
+    partial_seats: impl IntoIterator<Item = &'seats u32>,
+    weights: &mut Vec<(u32, u128)>,
+) -> Option<u64> {
+    let stake_per_seat = population.get_stake_per_seat();
+    weights.clear();
+    match config.corruption_metric {
+        CorruptionMetric::Stake => weights.extend(
+            seats.iter().map(|&idx| (idx, stake_per_seat)).chain(
+                partial_seats
+                    .into_iter()
+                    .map(|&idx| (idx, population.partial_seat_weight(idx))),
+            ),
+        ),
+        CorruptionMetric::Seats | CorruptionMetric::Validators => {
+            weights.extend(seats.iter().chain(partial_seats).map(|&idx| (idx, 1)))
+        }
+    }
+    // Sum the weights of each validator in the shard.
+    weights.sort_unstable_by_key(|&(idx, _)| idx);
+    weights.dedup_by(|(idx, weight), (prev_idx, prev_weight)| {
+        if idx == prev_idx {
+            *prev_weight += *weight;
+        }
+        idx == prev_idx
+    });
+    if config.corruption_metric == CorruptionMetric::Validators {
+        for (_, weight) in weights.iter_mut() {
+            *weight = 1;
+        }
+    }
+
+    let threshold = config.max_malicious_stake_per_shard[0];
+    let total_weight: u128 = weights.iter().map(|&(_, weight)| weight).sum();
+    let malicious_weight: u128 = weights
+        .iter()
+        .filter(|&&(idx, _)| population.is_malicious(idx))
+        .map(|&(_, weight)| weight)
+        .sum();
+    weights.retain(|&(idx, _)| !population.is_malicious(idx));
+    let honest = weights;
+
+    honest.sort_unstable_by_key(|&(_, weight)| std::cmp::Reverse(weight));
+    let by_weight = corruptions_in_order(
+        honest,
+        stakes,
+        budget,
+        malicious_weight,
+        total_weight,
+        threshold,
+    );
+    let CorruptionBudget::Stake(_) = budget else {
+        return by_weight;
+    };
+    // Validators holding seats have non-zero stake. Unreduced ratios compare correctly.
+    honest.sort_unstable_by(|&(idx, weight), &(other_idx, other_weight)| {
+        Ratio::new_raw(other_weight, stakes[other_idx as usize])
+            .cmp(&Ratio::new_raw(weight, stakes[idx as usize]))
+    });
+    let by_weight_per_stake = corruptions_in_order(
+        honest,
+        stakes,
+        budget,
+        malicious_weight,
+        total_weight,
+        threshold,
+    );
+    by_weight.into_iter().chain(by_weight_per_stake).min()
+}
+
+/// Corrupts `honest` validators in the given order until the malicious ratio exceeds `threshold`
+/// and returns the number of corruptions, or `None` if `budget` does not suffice.
+fn corruptions_in_order(
+    honest: &[(u32, u128)],
+    stakes: &[u128],
+    budget: CorruptionBudget,
+    mut malicious_weight: u128,
+    total_weight: u128,
+    threshold: Ratio<u128>,
+) -> Option<u64> {
+    let (mut num_corruptions, mut spent) = (0u64, 0u128);
+    for &(idx, weight) in honest {
+        if Ratio::new(malicious_weight, total_weight) > threshold {
+            break;
+        }
+        spent += match budget {
+            CorruptionBudget::Stake(_) => stakes[idx as usize],
+            CorruptionBudget::Validators(_) => 1,
+        };
+        let within_budget = match budget {
+            CorruptionBudget::Stake(amount) => spent <= amount,
+            CorruptionBudget::Validators(n) => spent <= u128::from(n),
+        };
+        if !within_budget {
+            return None;
+        }
+        num_corruptions += 1;
+        malicious_weight += weight;
+    }
+    (Ratio::new(malicious_weight, total_weight) > threshold).then_some(num_corruptions)
+}
+
+/// Returns the distribution of the time to corruption, with one row per number of corruptions
+/// observed.
+fn time_to_corruption(
+    counts: &CorruptionCounts,
+    corruption_delay: u64,
+) -> anyhow::Result<Vec<TimeToCorruptionRow>> {
+    let mut rows = vec![];
+    let mut cumulative_count = 0;
+    for (num_corruptions, &count) in (0u64..).zip(counts.num_corruptions.iter()) {
+        if count == 0 {
+            continue;
+        }
+        cumulative_count += count;
+        rows.push(TimeToCorruptionRow {
+            num_corruptions,
+            time: num_corruptions
+                .checked_mul(corruption_delay)
+                .ok_or_else(|| anyhow::anyhow!("time to corruption overflows u64"))?,
+            count,
+            probability: count as f64 / counts.num_blocks as f64,
+            cumulative_probability: cumulative_count as f64 / counts.num_blocks as f64,
+        });
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use num_rational::Ratio;
+
+    use super::{
+        corruptions_to_corrupt_shard, time_to_corruption, CorruptionBudget, CorruptionCounts,
+    };
+    use crate::compact::CompactPopulation;
+    use crate::config::Config;
+    use crate::shard::CorruptionMetric;
+    use crate::validator::parse_raw_validator_data;
+    use crate::validator::tests::new_test_raw_validator_data;
+
+    #[test]
+    fn test_corruptions_to_corrupt_shard() {
+        let mut config = Config::new_mock(false);
+        config.max_malicious_stake_per_shard = vec![Ratio::new(1, 2)];
+        let raw_validator_data = new_test_raw_validator_data();
+        let (_, validators) =
+            parse_raw_validator_data(&raw_validator_data, config.stake_per_seat.get()).unwrap();
+        let population = CompactPopulation::new(&validators, config.stake_per_seat.get()).unwrap();
+        let stakes: Vec<u128> = raw_validator_data.iter().map(|v| v.stake).collect();
+        let corruptions = |seats: &[u32], budget, metric| {
+            let config = Config {
+                corruption_metric: metric,
+                ..config.clone()
+            };
+            corruptions_to_corrupt_shard(
+                &config,
+                &population,
+                &stakes,
+                budget,
+                seats,
+                &[],
+                &mut Vec::new(),
+            )
+        };
+
+        // Validator 1 is malicious and holds 1 of 4 seats, validator 0 holds 2.
+        let seats = [0, 0, 1, 4];
+        let budget = CorruptionBudget::Validators(1);
+        assert_eq!(
+            corruptions(&seats, budget, CorruptionMetric::Stake),
+            Some(1)
+        );
+        assert_eq!(
+            corruptions(
+                &seats,
+                CorruptionBudget::Stake(400),
+                CorruptionMetric::Stake
+            ),
+            None
+        );
+        assert_eq!(
+            corruptions(
+                &seats,
+                CorruptionBudget::Validators(2),
+                CorruptionMetric::Validators
+            ),
+            Some(1)
+        );
+        // Validator 0 holds 2 of 6 seats but has a stake of 500. Corrupting the validators of the
+        // other 3 honest seats costs less stake.
+        let seats = [0, 0, 1, 4, 5, 6];
+        assert_eq!(
+            corruptions(
+                &seats,
+                CorruptionBudget::Stake(300),
+                CorruptionMetric::Stake
+            ),
+            Some(3)
+        );
+        assert_eq!(
+            corruptions(
+                &seats,
+                CorruptionBudget::Stake(600),
+                CorruptionMetric::Stake
+            ),
+            Some(2)
+        );
+        // Validators 1 and 3 are malicious.
+        assert_eq!(
+            corruptions(&[1, 3, 1, 4], budget, CorruptionMetric::Seats),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn test_time_to_corruption() {
+        let mut counts = CorruptionCounts::default();
+        for num_corruptions in [Some(2), Some(0), None, Some(2)] {
+            counts.add(num_corruptions);
+        }
+        let mut other = CorruptionCounts::default();
+        other.add(Some(5));
+        counts.merge(&other);
+
+        assert_eq!(counts.num_corruptions, vec![1, 0, 2, 0, 0, 1]);
+        insta::assert_yaml_snapshot!(time_to_corruption(&counts, 10).unwrap());
+    }
+}
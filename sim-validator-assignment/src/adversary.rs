@@ -0,0 +1,106 @@
+use crate::validation::ValidatorDataReport;
+use crate::validator::RawValidatorData;
+
+/// The prefix of the account ids of validators controlled by the adversary.
+const ADVERSARY_ACCOUNT_PREFIX: &str = "adversary_";
+
+/// Describes how the budget of the adversary is split into validators.
+#[derive(PartialEq, Debug)]
+pub struct AdversarySplit {
+    /// The number of validators holding exactly `stake_per_seat`.
+    pub num_full_seat_accounts: u64,
+    /// The stake which does not cover a full seat. It is held by one more validator if partial
+    /// seats are included, otherwise it is not used.
+    pub remainder: u128,
+}
+
+impl AdversarySplit {
+    pub fn new(budget: u128, stake_per_seat: u128) -> anyhow::Result<Self> {
+        if stake_per_seat == 0 {
+            anyhow::bail!("stake_per_seat must be greater than zero");
+        }
+        let num_full_seat_accounts = u64::try_from(budget / stake_per_seat).map_err(|_| {
+            anyhow::anyhow!("adversary budget {budget} yields more seats than supported")
+        })?;
+        Ok(Self {
+            num_full_seat_accounts,
+            remainder: budget % stake_per_seat,
+        })
+    }
+}
+
+/// Replaces the malicious validators of `raw_validator_data` by an adversary holding `budget`.
+///
+/// Since a validator's stake is rounded down to full seats, the adversary maximizes its seats by
+/// splitting its budget into validators holding exactly `stake_per_seat` each. The remaining stake
+/// is held by one more validator if `include_partial_seats` is set. Honest validators are kept as
+/// they are.
+pub fn replace_malicious_by_adversary(
+    raw_validator_data: &[RawValidatorData],
+    budget: u128,
+    stake_per_seat: u128,
+    include_partial_seats: bool,
+) -> anyhow::Result<(AdversarySplit, Vec<RawValidatorData>)> {
+    let split = AdversarySplit::new(budget, stake_per_seat)?;
+    let num_accounts =
+        split.num_full_seat_accounts + u64::from(include_partial_seats && split.remainder > 0);
+    // Validators are indexed by `u32` in compact form.
+    if num_accounts > u64::from(u32::MAX) {
+        anyhow::bail!(
+            "adversary budget {budget} would be split into {num_accounts} validators, at most {} are supported",
+            u32::MAX
+        );
+    }
+
+    let mut validators: Vec<RawValidatorData> = raw_validator_data
+        .iter()
+        .filter(|v| !v.is_malicious)
+        .cloned()
+        .collect();
+    let new_account = |idx: u64, stake: u128| RawValidatorData {
+        account_id: format!("{ADVERSARY_ACCOUNT_PREFIX}{idx}"),
+        stake,
+        is_malicious: true,
+    };
+    validators
+        .extend((0..split.num_full_seat_accounts).map(|idx| new_account(idx, stake_per_seat)));
+    if include_partial_seats && split.remainder > 0 {
+        validators.push(new_account(split.num_full_seat_accounts, split.remainder));
+    }
+
+    ValidatorDataReport::new(&validators).ensure_valid()?;
+    Ok((split, validators))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{replace_malicious_by_adversary, AdversarySplit};
+    use crate::validator::parse_raw_validator_data;
+    use crate::validator::tests::new_test_raw_validator_data;
+
+    #[test]
+    fn test_replace_malicious_by_adversary() {
+        let raw_validator_data = new_test_raw_validator_data();
+        let (split, validators) =
+            replace_malicious_by_adversary(&raw_validator_data, 450, 100, true).unwrap();
+        assert_eq!(
+            split,
+            AdversarySplit {
+                num_full_seat_accounts: 4,
+                remainder: 50
+            }
+        );
+        insta::assert_yaml_snapshot!(validators);
+
+        let (population_stats, _) = parse_raw_validator_data(&validators, 100).unwrap();
+        assert_eq!(population_stats.malicious_stake, 450);
+        assert_eq!(population_stats.malicious_seats, 4);
+
+        // Without partial seats the remainder is dropped.
+        let (_, validators) =
+            replace_malicious_by_adversary(&raw_validator_data, 450, 100, false).unwrap();
+        assert_eq!(validators.iter().filter(|v| v.is_malicious).count(), 4);
+
+        assert!(replace_malicious_by_adversary(&raw_validator_data, 450, 0, false).is_err());
+    }
+}
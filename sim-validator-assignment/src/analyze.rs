@@ -0,0 +1,323 @@
+use clap::Args;
+use num_rational::Ratio;
+use std::path::PathBuf;
+
+use crate::run::{check_seat_coverage, mock_validator_data};
+use crate::validation::validate_analyze_config;
+use crate::validator::{parse_raw_validator_data, read_validator_data, PopulationStats};
+
+#[derive(Args, Debug)]
+pub struct AnalyzeConfig {
+    #[arg(long)]
+    pub num_shards: u16,
+    #[arg(long)]
+    pub seats_per_shard: u64,
+    /// The amount of stake required to get one seat.
+    #[arg(long)]
+    pub stake_per_seat: u128,
+    /// If the ratio of malicious stake is higher than this threshold, the shard is considered
+    /// corrupted, i.e. a security failure occured. Multiple thresholds can be passed separated by
+    /// commas.
+    #[arg(long, value_delimiter = ',', required = true)]
+    pub max_malicious_stake_per_shard: Vec<Ratio<u128>>,
+    /// The file from which validator data is read. It is expected to contain a vector of
+    /// `RawValidatorData` serialized as JSON. If no validator data is provided, mocked validator
+    /// data will be used.
+    #[arg(long)]
+    pub validator_data: Option<PathBuf>,
+}
+
+/// Computes the probabilities of shard corruption analytically for the model with only full
+/// seats, i.e. partial seats are ignored.
+///
+/// Without partial seats every seat has the same stake, hence the composition of shards after a
+/// uniform shuffle of seats follows a multivariate hypergeometric distribution.
+pub fn analyze(config: &AnalyzeConfig) -> anyhow::Result<()> {
+    let raw_validator_data = match &config.validator_data {
+        Some(file_path) => read_validator_data(file_path.as_path())?,
+        None => mock_validator_data(),
+    };
+    validate_analyze_config(config, &raw_validator_data)?;
+    let (population_stats, _) =
+        parse_raw_validator_data(&raw_validator_data, config.stake_per_seat)?;
+    let models = config
+        .max_malicious_stake_per_shard
+        .iter()
+        .map(|&threshold| {
+            SeatModel::new(
+                &population_stats,
+                config.num_shards,
+                config.seats_per_shard,
+                threshold,
+            )
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    println!("seats\t{}", population_stats.seats);
+    println!("malicious_seats\t{}", population_stats.malicious_seats);
+    println!("max_malicious_stake_per_shard\tmax_malicious_seats_per_shard\tp(shard corrupted)\tp(any shard in block corrupted)\tunion bound of p(any shard in block corrupted)");
+    for (threshold, model) in config.max_malicious_stake_per_shard.iter().zip(models) {
+        let ln_shard = model.ln_shard_corruption_probability();
+        let ln_any_shard = model.ln_any_shard_corruption_probability();
+        // The union bound is `num_shards * p_shard`, capped at 1.
+        let ln_union_bound = (f64::from(config.num_shards).ln() + ln_shard).min(0.0);
+        println!(
+            "{threshold}\t{}\t{}\t{}\t{}",
+            model.max_malicious_seats_per_shard,
+            format_ln_probability(ln_shard),
+            format_ln_probability(ln_any_shard),
+            format_ln_probability(ln_union_bound)
+        );
+    }
+
+    Ok(())
+}
+
+/// Describes the assignment of seats with equal stake to shards.
+#[derive(Debug)]
+pub struct SeatModel {
+    seats: u64,
+    malicious_seats: u64,
+    num_shards: u16,
+    seats_per_shard: u64,
+    /// A shard is corrupted if it holds more than this number of malicious seats.
+    max_malicious_seats_per_shard: u64,
+    /// The `i`th element holds `ln(i!)`.
+    ln_factorials: Vec<f64>,
+}
+
+impl SeatModel {
+    pub fn new(
+        population_stats: &PopulationStats,
+        num_shards: u16,
+        seats_per_shard: u64,
+        max_malicious_stake_per_shard: Ratio<u128>,
+    ) -> anyhow::Result<Self> {
+        let required_seats = u64::from(num_shards)
+            .checked_mul(seats_per_shard)
+            .ok_or_else(|| anyhow::anyhow!("num_shards * seats_per_shard overflows u64"))?;
+        check_seat_coverage(population_stats, required_seats)?;
+
+        // All seats have equal stake, so the ratio of malicious stake equals the ratio of
+        // malicious seats. The threshold is converted to the largest number of malicious seats
+        // `m` with `m / seats_per_shard <= max_malicious_stake_per_shard`.
+        let max_malicious_seats = max_malicious_stake_per_shard
+            .numer()
+            .checked_mul(u128::from(seats_per_shard))
+            .ok_or_else(|| anyhow::anyhow!("max_malicious_stake_per_shard is too large"))?
+            / max_malicious_stake_per_shard.denom();
+        let max_malicious_seats_per_shard =
+            u64::try_from(max_malicious_seats.min(u128::from(seats_per_shard)))
+                .expect("value is bounded by seats_per_shard");
+
+        let num_factorials =
+            usize::try_from(population_stats.seats).expect("seats should fit into usize") + 1;
+        let mut ln_factorials = Vec::with_capacity(num_factorials);
+        ln_factorials.push(0.0);
+        for i in 1..num_factorials {
+            ln_factorials.push(ln_factorials[i - 1] + (i as f64).ln());
+        }
+
+        Ok(Self {
+            seats: population_stats.seats,
+            malicious_seats: population_stats.malicious_seats,
+            num_shards,
+            seats_per_shard,
+            max_malicious_seats_per_shard,
+            ln_factorials,
+        })
+    }
+
+    pub fn get_seats_per_shard(&self) -> u64 {
+        self.seats_per_shard
+    }
+
+    pub fn get_max_malicious_seats_per_shard(&self) -> u64 {
+        self.max_malicious_seats_per_shard
+    }
+
+    /// Returns the logarithms of the probability mass function of the number of malicious seats in
+    /// a particular shard. The `m`th element corresponds to `m` malicious seats.
+    pub fn ln_shard_pmf(&self) -> Vec<f64> {
+        let honest_seats = self.seats - self.malicious_seats;
+        (0..=self.seats_per_shard)
+            .map(|m| self.ln_pmf(self.malicious_seats, honest_seats, m))
+            .collect()
+    }
+
+    /// Returns the natural logarithm of the probability that a particular shard is corrupted.
+    pub fn ln_shard_corruption_probability(&self) -> f64 {
+        self.ln_tail(self.malicious_seats, self.seats - self.malicious_seats)
+    }
+
+    /// Returns the natural logarithm of the probability that at least one shard of a block is
+    /// corrupted.
+    ///
+    /// Shards are drawn one after another from the seats that remain. The probability is the sum
+    /// over `j` of the probability that shards `0..j` are not corrupted and shard `j` is
+    /// corrupted. Summing only non-negative terms avoids the cancellation in `1 - p(no shard
+    /// corrupted)`, which matters for tiny probabilities.
+    pub fn ln_any_shard_corruption_probability(&self) -> f64 {
+        let max_malicious = self.max_malicious_seats_per_shard;
+        // The `u`th element holds the logarithm of the probability that all shards drawn so far
+        // are not corrupted and hold `u` malicious seats in total.
+        let mut ln_not_corrupted = vec![0.0];
+        let mut ln_any_corrupted = f64::NEG_INFINITY;
+
+        for shard_idx in 0..u64::from(self.num_shards) {
+            let drawn_seats = shard_idx * self.seats_per_shard;
+            let mut ln_next =
+                vec![f64::NEG_INFINITY; ln_not_corrupted.len() + max_malicious as usize];
+
+            for (drawn_malicious, &ln_p) in (0u64..).zip(ln_not_corrupted.iter()) {
+                if ln_p == f64::NEG_INFINITY {
+                    continue;
+                }
+                let malicious = self.malicious_seats - drawn_malicious;
+                let honest = self.seats - self.malicious_seats - (drawn_seats - drawn_malicious);
+
+                ln_any_corrupted = ln_add(ln_any_corrupted, ln_p + self.ln_tail(malicious, honest));
+                for m in 0..=max_malicious.min(malicious) {
+                    let idx = (drawn_malicious + m) as usize;
+                    ln_next[idx] = ln_add(ln_next[idx], ln_p + self.ln_pmf(malicious, honest, m));
+                }
+            }
+
+            ln_not_corrupted = ln_next;
+        }
+
+        ln_any_corrupted
+    }
+
+    /// Returns `ln(C(n, k))`.
+    fn ln_binomial(&self, n: u64, k: u64) -> f64 {
+        if k > n {
+            return f64::NEG_INFINITY;
+        }
+        self.ln_factorials[n as usize]
+            - self.ln_factorials[k as usize]
+            - self.ln_factorials[(n - k) as usize]
+    }
+
+    /// Returns the logarithm of the probability that a shard drawn from `malicious + honest`
+    /// seats holds exactly `m` malicious seats.
+    fn ln_pmf(&self, malicious: u64, honest: u64, m: u64) -> f64 {
+        if m > self.seats_per_shard {
+            return f64::NEG_INFINITY;
+        }
+        self.ln_binomial(malicious, m) + self.ln_binomial(honest, self.seats_per_shard - m)
+            - self.ln_binomial(malicious + honest, self.seats_per_shard)
+    }
+
+    /// Returns the logarithm of the probability that a shard drawn from `malicious + honest`
+    /// seats is corrupted.
+    fn ln_tail(&self, malicious: u64, honest: u64) -> f64 {
+        let max_m = self.seats_per_shard.min(malicious);
+        (self.max_malicious_seats_per_shard + 1..=max_m).fold(f64::NEG_INFINITY, |acc, m| {
+            ln_add(acc, self.ln_pmf(malicious, honest, m))
+        })
+    }
+}
+
+/// Returns `ln(exp(a) + exp(b))` without leaving log space.
+pub(crate) fn ln_add(a: f64, b: f64) -> f64 {
+    let (max, min) = if a > b { (a, b) } else { (b, a) };
+    if min == f64::NEG_INFINITY {
+        return max;
+    }
+    max + (min - max).exp().ln_1p()
+}
+
+/// Formats the probability `exp(ln_p)` in scientific notation. Probabilities too small to be
+/// represented by `f64` are formatted correctly as well.
+pub fn format_ln_probability(ln_p: f64) -> String {
+    if ln_p == f64::NEG_INFINITY {
+        return "0".to_owned();
+    }
+    let log10_p = ln_p / std::f64::consts::LN_10;
+    let exponent = log10_p.floor();
+    let mantissa = 10f64.powf(log10_p - exponent);
+    format!("{mantissa:.4}e{exponent}")
+}
+
+#[cfg(test)]
+mod tests {
+    use num_rational::Ratio;
+
+    use super::{format_ln_probability, SeatModel};
+    use crate::validator::PopulationStats;
+
+    fn new_test_model(
+        seats: u64,
+        malicious_seats: u64,
+        num_shards: u16,
+        seats_per_shard: u64,
+        threshold: Ratio<u128>,
+    ) -> SeatModel {
+        let population_stats = PopulationStats {
+            seats,
+            malicious_seats,
+            ..Default::default()
+        };
+        SeatModel::new(&population_stats, num_shards, seats_per_shard, threshold).unwrap()
+    }
+
+    fn assert_probability(ln_p: f64, expected: f64) {
+        assert!(
+            (ln_p.exp() - expected).abs() < 1e-12,
+            "expected {expected}, got {}",
+            ln_p.exp()
+        );
+    }
+
+    #[test]
+    fn test_small_model() {
+        // 6 seats of which 2 are malicious, 2 shards with 2 seats each. A shard is corrupted if
+        // both its seats are malicious.
+        let model = new_test_model(6, 2, 2, 2, Ratio::new(1, 2));
+        assert_eq!(model.max_malicious_seats_per_shard, 1);
+        // C(2, 2) * C(4, 0) / C(6, 2)
+        assert_probability(model.ln_shard_corruption_probability(), 1.0 / 15.0);
+        // Shard 0 is corrupted or shard 0 holds no malicious seat and shard 1 is corrupted.
+        assert_probability(
+            model.ln_any_shard_corruption_probability(),
+            1.0 / 15.0 + 6.0 / 15.0 * 1.0 / 6.0,
+        );
+    }
+
+    #[test]
+    fn test_single_shard() {
+        let model = new_test_model(100, 40, 1, 10, Ratio::new(1, 3));
+        assert_probability(
+            model.ln_any_shard_corruption_probability(),
+            model.ln_shard_corruption_probability().exp(),
+        );
+    }
+
+    #[test]
+    fn test_no_corruption_possible() {
+        let model = new_test_model(10, 3, 2, 5, Ratio::new(2, 3));
+        assert_eq!(model.ln_shard_corruption_probability(), f64::NEG_INFINITY);
+        assert_eq!(
+            model.ln_any_shard_corruption_probability(),
+            f64::NEG_INFINITY
+        );
+    }
+
+    #[test]
+    fn test_not_enough_seats() {
+        let population_stats = PopulationStats {
+            seats: 5,
+            ..Default::default()
+        };
+        assert!(SeatModel::new(&population_stats, 2, 3, Ratio::new(1, 2)).is_err());
+    }
+
+    #[test]
+    fn test_format_ln_probability() {
+        assert_eq!(format_ln_probability(f64::NEG_INFINITY), "0");
+        assert_eq!(format_ln_probability(0.0), "1.0000e0");
+        assert_eq!(format_ln_probability(0.25f64.ln()), "2.5000e-1");
+        assert_eq!(format_ln_probability(-1000.0), "5.0760e-435");
+    }
+}
@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{read_to_string, rename, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::counters::Counters;
+use crate::rng::RngState;
+use crate::validator::RawValidatorData;
+
+/// Fields of [`Config`] which may differ between a run and the run resuming it, since they do not
+/// affect the simulated blocks.
+const RESUMABLE_CONFIG_FIELDS: [&str; 11] = [
+    "config",
+    "num_blocks",
+    "target_relative_error",
+    "max_blocks",
+    "seed",
+    "histogram_output",
+    "histogram_format",
+    "output",
+    "format",
+    "checkpoint",
+    "resume",
+];
+
+/// The progress of a simulation, from which it can be resumed.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Checkpoint {
+    /// The hash of the config, see [`config_hash`].
+    pub config_hash: u64,
+    /// The hash of the validator data, see [`validator_data_hash`].
+    pub validator_data_hash: u64,
+    pub seed: u64,
+    /// The corruption events observed so far, including the number of simulated blocks.
+    pub counters: Counters,
+    /// The state of the rng of every thread.
+    pub rng_states: Vec<RngState>,
+}
+
+impl Checkpoint {
+    pub fn read(file_path: &Path) -> anyhow::Result<Self> {
+        let file_content = read_to_string(file_path)?;
+        Ok(serde_json::from_str(&file_content)?)
+    }
+
+    /// Writes the checkpoint to `file_path`. The file is replaced atomically, hence a checkpoint
+    /// is not corrupted if the process is killed while writing it.
+    pub fn write(&self, file_path: &Path) -> anyhow::Result<()> {
+        let mut tmp_path = PathBuf::from(file_path);
+        tmp_path.as_mut_os_string().push(".tmp");
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(serde_json::to_string(self)?.as_bytes())?;
+        file.sync_all()?;
+        rename(&tmp_path, file_path)?;
+        Ok(())
+    }
+
+    /// Checks that a simulation with `config` and `raw_validator_data` may resume from this
+    /// checkpoint.
+    pub fn check_resumable(
+        &self,
+        config: &Config,
+        raw_validator_data: &[RawValidatorData],
+    ) -> anyhow::Result<()> {
+        if self.config_hash != config_hash(config)? {
+            anyhow::bail!("the config differs from the one of the checkpoint");
+        }
+        if self.validator_data_hash != validator_data_hash(raw_validator_data)? {
+            anyhow::bail!("the validator data differs from the one of the checkpoint");
+        }
+        if config.seed.is_some_and(|seed| seed != self.seed) {
+            anyhow::bail!(
+                "seed {} differs from seed {} of the checkpoint",
+                config.seed.unwrap_or_default(),
+                self.seed
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Returns a hash of the fields of `config` which affect the simulated blocks.
+pub fn config_hash(config: &Config) -> anyhow::Result<u64> {
+    let mut value = serde_json::to_value(config)?;
+    if let Some(fields) = value.as_object_mut() {
+        for field in RESUMABLE_CONFIG_FIELDS {
+            fields.remove(field);
+        }
+    }
+    Ok(fnv1a(value.to_string().as_bytes()))
+}
+
+pub fn validator_data_hash(raw_validator_data: &[RawValidatorData]) -> anyhow::Result<u64> {
+    Ok(fnv1a(&serde_json::to_vec(raw_validator_data)?))
+}
+
+/// The 64-bit FNV-1a hash, which is stable across platforms and Rust versions (unlike
+/// [`std::hash::DefaultHasher`]).
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{config_hash, fnv1a};
+    use crate::config::Config;
+    use num_rational::Ratio;
+
+    #[test]
+    fn test_fnv1a() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn test_config_hash() {
+        let config = Config::new_mock(false);
+        let mut resumable = Config::new_mock(false);
+        resumable.num_blocks = Some(1_000_000);
+        resumable.seed = Some(42);
+        assert_eq!(
+            config_hash(&config).unwrap(),
+            config_hash(&resumable).unwrap()
+        );
+
+        let mut different = Config::new_mock(false);
+        different.max_malicious_stake_per_shard = vec![Ratio::new(1, 2)];
+        assert_ne!(
+            config_hash(&config).unwrap(),
+            config_hash(&different).unwrap()
+        );
+    }
+}
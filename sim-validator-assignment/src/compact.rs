@@ -0,0 +1,178 @@
+use crate::validator::Validator;
+
+/// A compact representation of a set of validators, designed for the hot loop of simulations.
+///
+/// Seats are represented by the `u32` index of the validator holding them instead of by
+/// [`Seat`](crate::seat::Seat) and [`PartialSeat`](crate::partial_seat::PartialSeat), which
+/// reference a [`Validator`]. Looking up whether a seat is malicious or the weight of a partial
+/// seat then accesses small, contiguous tables.
+#[derive(Debug)]
+pub struct CompactPopulation {
+    stake_per_seat: u128,
+    /// Bit `i % 64` of `malicious[i / 64]` is set if `validators[i]` is malicious.
+    malicious: Vec<u64>,
+    /// The `i`th element holds the weight of the partial seat of `validators[i]`.
+    partial_seat_weights: Vec<u128>,
+    /// The seats of all validators, with the seats of `validators[i]` preceding those of
+    /// `validators[i + 1]`.
+    ordered_seats: Vec<u32>,
+    /// The partial seats with non-zero weight of all validators, ordered by validator index.
+    ordered_partial_seats: Vec<u32>,
+}
+
+impl CompactPopulation {
+    /// Constructs the compact representation of `validators`. The index of a validator in
+    /// `validators` identifies its seats.
+    pub fn new(validators: &[Validator], stake_per_seat: u128) -> anyhow::Result<Self> {
+        if u32::try_from(validators.len()).is_err() {
+            anyhow::bail!(
+                "{} validators exceed the maximum of {} supported by compact seats",
+                validators.len(),
+                u32::MAX
+            );
+        }
+
+        let mut malicious = vec![0; validators.len().div_ceil(64)];
+        let mut partial_seat_weights = Vec::with_capacity(validators.len());
+        let mut ordered_seats = vec![];
+        let mut ordered_partial_seats = vec![];
+
+        for (idx, v) in (0u32..).zip(validators.iter()) {
+            if v.get_is_malicious() {
+                malicious[idx as usize / 64] |= 1 << (idx % 64);
+            }
+            ordered_seats.extend(std::iter::repeat_n(idx, v.num_seats_as_usize()));
+
+            let weight = v.partial_seat(stake_per_seat).get_weight();
+            partial_seat_weights.push(weight);
+            if weight > 0 {
+                ordered_partial_seats.push(idx);
+            }
+        }
+
+        Ok(Self {
+            stake_per_seat,
+            malicious,
+            partial_seat_weights,
+            ordered_seats,
+            ordered_partial_seats,
+        })
+    }
+
+    pub fn get_stake_per_seat(&self) -> u128 {
+        self.stake_per_seat
+    }
+
+    /// Returns whether the validator with index `validator_idx` is malicious.
+    pub fn is_malicious(&self, validator_idx: u32) -> bool {
+        let idx = validator_idx as usize;
+        self.malicious[idx / 64] & (1 << (idx % 64)) != 0
+    }
+
+    /// Returns the weight of the partial seat held by the validator with index `validator_idx`.
+    pub fn partial_seat_weight(&self, validator_idx: u32) -> u128 {
+        self.partial_seat_weights[validator_idx as usize]
+    }
+
+    /// Returns the seats of all validators, equivalent to
+    /// [`new_ordered_seats`](crate::validator::new_ordered_seats).
+    pub fn new_ordered_seats(&self) -> Vec<u32> {
+        self.ordered_seats.clone()
+    }
+
+    /// Returns the partial seats with non-zero weight of all validators, equivalent to
+    /// [`new_ordered_partial_seats`](crate::validator::new_ordered_partial_seats).
+    pub fn new_ordered_partial_seats(&self) -> Vec<u32> {
+        self.ordered_partial_seats.clone()
+    }
+
+    /// Returns the number of malicious seats among `seats`.
+    pub fn count_malicious(&self, seats: &[u32]) -> u64 {
+        seats
+            .iter()
+            .map(|&validator_idx| u64::from(self.is_malicious(validator_idx)))
+            .sum()
+    }
+}
+
+/// A set of validators of a [`CompactPopulation`], identified by their index and stored as a
+/// bitset.
+///
+/// It is constructed once and reused, e.g. to count the distinct validators of every shard
+/// without allocating in the hot loop of simulations.
+#[derive(Debug)]
+pub struct ValidatorSet {
+    /// Bit `i % 64` of `bits[i / 64]` is set if the validator with index `i` is in the set.
+    bits: Vec<u64>,
+}
+
+impl ValidatorSet {
+    /// Constructs an empty set which can hold any validator of `population`.
+    pub fn new(population: &CompactPopulation) -> Self {
+        Self {
+            bits: vec![0; population.malicious.len()],
+        }
+    }
+
+    /// Inserts the validator with index `validator_idx` and returns whether it was not yet in the
+    /// set.
+    pub fn insert(&mut self, validator_idx: u32) -> bool {
+        let idx = validator_idx as usize;
+        let mask = 1 << (idx % 64);
+        let is_new = self.bits[idx / 64] & mask == 0;
+        self.bits[idx / 64] |= mask;
+        is_new
+    }
+
+    /// Removes the validator with index `validator_idx` from the set.
+    pub fn remove(&mut self, validator_idx: u32) {
+        let idx = validator_idx as usize;
+        self.bits[idx / 64] &= !(1 << (idx % 64));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CompactPopulation, ValidatorSet};
+    use crate::config::Config;
+    use crate::validator::parse_raw_validator_data;
+    use crate::validator::tests::new_test_raw_validator_data;
+
+    #[test]
+    fn test_compact_population() {
+        let config = Config::new_mock(true);
+        let (_, validators) =
+            parse_raw_validator_data(&new_test_raw_validator_data(), config.stake_per_seat.get())
+                .unwrap();
+        let population = CompactPopulation::new(&validators, config.stake_per_seat.get()).unwrap();
+
+        let seats = population.new_ordered_seats();
+        assert_eq!(&seats[0..7], &[0, 0, 0, 0, 0, 1, 1]);
+        assert_eq!(population.count_malicious(&seats), 4);
+        assert_eq!(population.new_ordered_partial_seats(), vec![1, 2]);
+
+        assert!(!population.is_malicious(0));
+        assert!(population.is_malicious(1));
+        assert!(population.is_malicious(3));
+        assert_eq!(population.partial_seat_weight(0), 0);
+        assert_eq!(population.partial_seat_weight(1), 10);
+        assert_eq!(population.partial_seat_weight(2), 90);
+    }
+
+    #[test]
+    fn test_validator_set() {
+        let config = Config::new_mock(true);
+        let (_, validators) =
+            parse_raw_validator_data(&new_test_raw_validator_data(), config.stake_per_seat.get())
+                .unwrap();
+        let population = CompactPopulation::new(&validators, config.stake_per_seat.get()).unwrap();
+
+        let mut set = ValidatorSet::new(&population);
+        assert!(set.insert(3));
+        assert!(!set.insert(3));
+        assert!(set.insert(11));
+        set.remove(3);
+        assert!(set.insert(3));
+        assert!(!set.insert(11));
+    }
+}
@@ -1,40 +1,177 @@
-use clap::Args;
+use clap::builder::ArgPredicate;
+use clap::{ArgAction, Args};
 use num_rational::Ratio;
 use serde::Serialize;
+use std::iter::StepBy;
 use std::path::PathBuf;
+use std::slice::Iter;
 
-use crate::{partial_seat::PartialSeat, seat::Seat};
+use crate::malicious::MaliciousRule;
+use crate::output::OutputFormat;
+use crate::rng::RngBackend;
+use crate::seat_price::StakePerSeat;
+use crate::shard::CorruptionMetric;
 
-#[derive(Args, Serialize, Debug)]
+#[derive(Args, Serialize, Clone, Debug)]
 pub struct Config {
+    /// A scenario file from which the fields of this config are read, parsed as JSON if its
+    /// extension is `json` and as TOML otherwise. Fields are named like the flags but in snake
+    /// case, e.g. `num_shards = 4`. Flags passed on the command line override values of the file,
+    /// booleans can be switched off with e.g. `--include-partial-seats=false`.
     #[arg(long)]
-    pub num_blocks: u64,
+    pub config: Option<PathBuf>,
+    /// The number of blocks to simulate.
+    #[arg(
+        long,
+        required_unless_present_any = ["target_relative_error", "config"],
+        conflicts_with = "target_relative_error"
+    )]
+    pub num_blocks: Option<u64>,
+    /// Simulate blocks until the relative error of the estimated probability of shard corruption
+    /// drops below this value. The relative error is half the width of the 95% confidence
+    /// interval divided by the estimate.
+    #[arg(long, requires = "max_blocks")]
+    pub target_relative_error: Option<f64>,
+    /// The maximum number of blocks to simulate when `target_relative_error` is set. The
+    /// simulation stops after this number of blocks even if the target was not reached.
+    #[arg(
+        long,
+        requires = "target_relative_error",
+        conflicts_with = "num_blocks"
+    )]
+    pub max_blocks: Option<u64>,
     // Using `u16` because it allows infallible conversion to `usize` (which is not the case for
     // unsigned integer types with more bits, e.g. `u32`). For use cases of this simulation the
     // number of shards is expected to be less than `u16::MAX`.
-    #[arg(long)]
+    //
+    // Required arguments may be set in the scenario file instead. If it is passed, they default to
+    // placeholders which are replaced by `scenario::apply_scenario`.
+    #[arg(
+        long,
+        required = false,
+        required_unless_present = "config",
+        default_value_if("config", ArgPredicate::IsPresent, "0")
+    )]
     pub num_shards: u16,
     /// The set of validators must be sufficient to fill `num_shards * seats_per_shard` seats. Any
     /// seats above that threshold remain unassigned.
-    #[arg(long)]
+    #[arg(
+        long,
+        required = false,
+        required_unless_present = "config",
+        default_value_if("config", ArgPredicate::IsPresent, "0")
+    )]
     pub seats_per_shard: u64,
-    /// The amount of stake required to get one seat.
-    #[arg(long)]
-    pub stake_per_seat: u128,
+    /// The amount of stake required to get one seat. Pass `auto` to compute it from validator data
+    /// such that validators cover `num_shards * seats_per_shard` seats, following the seat price
+    /// algorithm of the NEAR protocol.
+    #[arg(
+        long,
+        required = false,
+        required_unless_present = "config",
+        default_value_if("config", ArgPredicate::IsPresent, "0")
+    )]
+    pub stake_per_seat: StakePerSeat,
     /// If the ratio of malicious stake is higher than this threshold, the shard is considered
-    /// corrupted, i.e. a security failure occured.
-    #[arg(long)]
-    pub max_malicious_stake_per_shard: Ratio<u128>,
+    /// corrupted, i.e. a security failure occured. Multiple thresholds can be passed separated by
+    /// commas, e.g. `1/3,2/3`, in which case corruption is evaluated against each of them for the
+    /// same shuffles of seats.
+    #[arg(long, value_delimiter = ',', required_unless_present = "config")]
+    pub max_malicious_stake_per_shard: Vec<Ratio<u128>>,
+    /// If the ratio of malicious stake is higher than this threshold, malicious validators can
+    /// stall the shard, i.e. its liveness is compromised.
+    #[arg(long, default_value = "1/3")]
+    pub liveness_threshold: Ratio<u128>,
+    /// If the ratio of malicious stake is higher than this threshold, malicious validators can
+    /// finalize invalid state in the shard, i.e. its safety is compromised.
+    #[arg(long, default_value = "2/3")]
+    pub safety_threshold: Ratio<u128>,
+    /// How the malicious share of a shard is measured. The thresholds above apply to the ratio of
+    /// malicious stake, seats or distinct validators in the shard.
+    #[arg(long, value_enum, default_value_t = CorruptionMetric::Stake)]
+    pub corruption_metric: CorruptionMetric,
     /// The file from which validator data is read. It is expected to contain a vector of
     /// `RawValidatorData` serialized as JSON. If no validator data is provided, mocked validator
     /// data will be used in the simulation.
     #[arg(long)]
     pub validator_data: Option<PathBuf>,
+    /// Choose malicious validators by a rule instead of reading `is_malicious` from validator
+    /// data. Rules are `largest:<n>`, `smallest:<n>`, `random-stake:<ratio>`,
+    /// `accounts:<id>,<id>,...` and `regex:<pattern>`. The `random-stake` rule is applied with the
+    /// rng seeded by `seed`.
+    #[arg(long)]
+    pub mark_malicious: Option<MaliciousRule>,
+    /// Replace the malicious validators of validator data by an adversary holding this amount of
+    /// stake. The adversary splits it into validators holding exactly `stake_per_seat` each, which
+    /// maximizes its number of seats, hence the simulation yields the worst case for this budget.
+    /// Requires a fixed `stake_per_seat`.
+    #[arg(long, conflicts_with = "mark_malicious")]
+    pub adversary_budget: Option<u128>,
     /// A validator's stake might not entirely cover seats given a particular `stake_per_seat`. This
     /// option controls whether remaining stake (not covering a full seat) should be assigned to a
     /// partial seat or ignored.
-    #[arg(long, default_value_t = false)]
+    #[arg(long, action = ArgAction::Set, num_args = 0..=1, default_value_t = false, default_missing_value = "true")]
     pub include_partial_seats: bool,
+    /// The seed of the random number generator used to shuffle seats. Running a simulation again
+    /// with the seed of a previous run reproduces its results. If no seed is provided, a random
+    /// seed is used.
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// The source of randomness used to shuffle seats.
+    #[arg(long, value_enum, default_value_t = RngBackend::Fastrand)]
+    pub rng: RngBackend,
+    /// The file from which the `replay` rng reads the values it replays. It is expected to contain
+    /// a vector of `u64` serialized as JSON.
+    #[arg(long, required_if_eq("rng", "replay"))]
+    pub replay_file: Option<PathBuf>,
+    /// The number of threads simulating blocks in parallel. Each thread uses its own stream of
+    /// randomness derived from the seed, hence results are reproducible only for the same seed
+    /// _and_ the same number of threads.
+    #[arg(long, default_value_t = 1)]
+    pub threads: u16,
+    /// Instead of simulating blocks, estimate the probability that a shard is corrupted with
+    /// importance sampling, drawing one shard per block. This is suitable for rare corruption
+    /// events, which a plain simulation would hardly ever observe.
+    #[arg(long, action = ArgAction::Set, num_args = 0..=1, default_value_t = false, default_missing_value = "true")]
+    pub importance_sampling: bool,
+    /// Collect a histogram of the malicious ratio (e.g. `malicious_stake / stake`, depending on
+    /// `corruption_metric`) across all simulated shards, using this
+    /// number of equal-width buckets over `[0, 1]`.
+    #[arg(
+        long,
+        requires = "histogram_output",
+        conflicts_with = "importance_sampling"
+    )]
+    pub histogram_buckets: Option<u16>,
+    /// The file to which the histogram of the malicious ratio is written.
+    #[arg(long, requires = "histogram_buckets")]
+    pub histogram_output: Option<PathBuf>,
+    /// The format in which the histogram of the malicious ratio is written.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Csv)]
+    pub histogram_format: OutputFormat,
+    /// The file to which a machine-readable record of the result is written. It contains the
+    /// config, population stats, seed, counts, estimated probabilities with confidence intervals
+    /// and the wall-clock time of the simulation.
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+    /// The format in which the result is written to `output`.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Json)]
+    pub format: OutputFormat,
+    /// The file to which the progress of the simulation is written after every heartbeat. It
+    /// allows to continue a simulation with `resume` if the process is killed.
+    #[arg(long, conflicts_with = "importance_sampling")]
+    pub checkpoint: Option<PathBuf>,
+    /// Resume the simulation from `checkpoint`. The config and validator data must match those of
+    /// the checkpointed simulation, except for the number of blocks and output options.
+    #[arg(
+        long,
+        action = ArgAction::Set,
+        num_args = 0..=1,
+        default_value_t = false,
+        default_missing_value = "true",
+        requires = "checkpoint"
+    )]
+    pub resume: bool,
 }
 
 /// Returns the number of (full) seats that can be claimed by `stake`.
@@ -48,16 +185,48 @@ impl Config {
     #[cfg(test)]
     pub fn new_mock(include_partial_seats: bool) -> Self {
         Self {
-            num_blocks: 1_000,
+            config: None,
+            num_blocks: Some(1_000),
+            target_relative_error: None,
+            max_blocks: None,
             num_shards: 4,
             seats_per_shard: 2,
-            stake_per_seat: 100,
-            max_malicious_stake_per_shard: Ratio::new(1, 3),
+            stake_per_seat: StakePerSeat::Fixed(100),
+            max_malicious_stake_per_shard: vec![Ratio::new(1, 3)],
+            liveness_threshold: Ratio::new(1, 3),
+            safety_threshold: Ratio::new(2, 3),
+            corruption_metric: CorruptionMetric::Stake,
             validator_data: None,
+            mark_malicious: None,
+            adversary_budget: None,
             include_partial_seats,
+            seed: None,
+            rng: RngBackend::Fastrand,
+            replay_file: None,
+            threads: 1,
+            importance_sampling: false,
+            histogram_buckets: None,
+            histogram_output: None,
+            histogram_format: OutputFormat::Csv,
+            output: None,
+            format: OutputFormat::Json,
+            checkpoint: None,
+            resume: false,
         }
     }
 
+    /// Returns the maximum number of blocks to simulate.
+    pub fn block_limit(&self) -> u64 {
+        self.num_blocks.or(self.max_blocks).unwrap_or_default()
+    }
+
+    /// Returns whether a simulation with the given `relative_error` may stop before reaching
+    /// [`Self::block_limit`].
+    pub fn is_precise_enough(&self, relative_error: f64) -> bool {
+        self.target_relative_error
+            .is_some_and(|target| relative_error <= target)
+    }
+
     /// Returns the amount of seats for all shards that must be filled by validators.
     pub fn total_seats(&self) -> u64 {
         u64::from(self.num_shards)
@@ -65,17 +234,16 @@ impl Config {
             .expect("min_required_seats should fit into return type")
     }
 
-    /// Collects the (consecutive) seats required for `shard_idx` starting from `seats[shard_idx *
+    /// Returns the (consecutive) seats required for `shard_idx` starting from `seats[shard_idx *
     /// self.seats_per_shard]`.
     ///
-    /// # Panics
-    ///
-    /// If `shard_idx` is out of bounds or if `seats` contains not enough seats.
-    pub fn collect_seats_for_shard<'seats>(
+    /// Seats may be represented by [`Seat`](crate::seat::Seat) or in compact form by validator
+    /// indices.
+    pub fn collect_seats_for_shard<'seats, T>(
         &self,
         shard_idx: usize,
-        seats: &'seats [Seat],
-    ) -> anyhow::Result<Vec<&Seat<'seats>>> {
+        seats: &'seats [T],
+    ) -> anyhow::Result<&'seats [T]> {
         if shard_idx >= usize::from(self.num_shards) {
             anyhow::bail!(
                 "shard_idx {} is an invalid index for {} shards",
@@ -95,13 +263,15 @@ impl Config {
 
         let seats_per_shard = usize::try_from(self.seats_per_shard).unwrap();
         let start = shard_idx * seats_per_shard;
-        let shard_seats: Vec<_> = seats[start..start + seats_per_shard].iter().collect();
 
-        Ok(shard_seats)
+        Ok(&seats[start..start + seats_per_shard])
     }
 
     /// Collect partials seats for `shard_idx` by picking seats from positions with `position %
-    /// num_shards == shard_idx`.
+    /// num_shards == shard_idx`. The seats are returned as an iterator to avoid allocations.
+    ///
+    /// Partial seats may be represented by [`PartialSeat`](crate::partial_seat::PartialSeat) or in
+    /// compact form by validator indices.
     ///
     /// # Motivation for assignment via modulo
     ///
@@ -114,11 +284,11 @@ impl Config {
     ///
     /// It is not needed since partial seats are included only to distribute leftover stake (not
     /// covering full seats) to shards.
-    pub fn collect_partial_seats_for_shard<'seats>(
+    pub fn collect_partial_seats_for_shard<'seats, T>(
         &self,
         shard_idx: usize,
-        partial_seats: &'seats [PartialSeat],
-    ) -> anyhow::Result<Vec<&PartialSeat<'seats>>> {
+        partial_seats: &'seats [T],
+    ) -> anyhow::Result<StepBy<Iter<'seats, T>>> {
         if shard_idx >= usize::from(self.num_shards) {
             anyhow::bail!(
                 "shard_idx {} is an invalid index for {} shards",
@@ -127,10 +297,11 @@ impl Config {
             )
         }
 
-        let mut shard_partial_seats = vec![];
-        for idx in (shard_idx..partial_seats.len()).step_by(self.num_shards.into()) {
-            shard_partial_seats.push(&partial_seats[idx]);
-        }
+        let shard_partial_seats = partial_seats
+            .get(shard_idx..)
+            .unwrap_or_default()
+            .iter()
+            .step_by(self.num_shards.into());
 
         Ok(shard_partial_seats)
     }
@@ -141,6 +312,8 @@ mod tests {
     use std::collections::BTreeMap;
 
     use super::{seats_per_stake, Config};
+    use crate::seat::Seat;
+    use crate::seat_price::StakePerSeat;
     use crate::validator::tests::new_test_raw_validator_data;
     use crate::validator::{
         new_ordered_partial_seats, new_ordered_seats, parse_raw_validator_data,
@@ -164,7 +337,8 @@ mod tests {
     fn test_collect_seats_for_shard() {
         let config = Config::new_mock(false);
         let (_, validators) =
-            parse_raw_validator_data(&new_test_raw_validator_data(), config.stake_per_seat);
+            parse_raw_validator_data(&new_test_raw_validator_data(), config.stake_per_seat.get())
+                .unwrap();
         // Using ordered seats as input to have a deterministic result of `collect_seats_for_shard`.
         let seats = new_ordered_seats(&validators);
 
@@ -186,29 +360,32 @@ mod tests {
     fn test_collect_seats_for_shard_errors() {
         let config = Config::new_mock(false);
         let (_, validators) =
-            parse_raw_validator_data(&new_test_raw_validator_data(), config.stake_per_seat);
+            parse_raw_validator_data(&new_test_raw_validator_data(), config.stake_per_seat.get())
+                .unwrap();
         let seats = new_ordered_seats(&validators);
 
         insta::assert_debug_snapshot!(config.collect_seats_for_shard(4, &seats));
-        insta::assert_debug_snapshot!(config.collect_seats_for_shard(0, &[]));
+        insta::assert_debug_snapshot!(config.collect_seats_for_shard::<Seat>(0, &[]));
     }
 
     #[test]
     fn test_collect_partial_seats_for_shard() {
         let mut config = Config::new_mock(true);
-        config.stake_per_seat = 90;
+        config.stake_per_seat = StakePerSeat::Fixed(90);
         let (_, validators) =
-            parse_raw_validator_data(&new_test_raw_validator_data(), config.stake_per_seat);
+            parse_raw_validator_data(&new_test_raw_validator_data(), config.stake_per_seat.get())
+                .unwrap();
         // Using ordered partial seats as input to have a deterministic result of
         // `collect_partial_seats_for_shard`.
-        let partial_seats = new_ordered_partial_seats(&validators, config.stake_per_seat);
+        let partial_seats = new_ordered_partial_seats(&validators, config.stake_per_seat.get());
 
         // Using `BTreeMap` for deterministic ordering of keys.
         let mut assignments = BTreeMap::new();
         for shard_idx in 0..config.num_shards {
             let assignment = config
                 .collect_partial_seats_for_shard(shard_idx.into(), &partial_seats)
-                .unwrap();
+                .unwrap()
+                .collect::<Vec<_>>();
             assignments.insert(format!("shard_{shard_idx}"), assignment);
         }
 
@@ -227,8 +404,9 @@ mod tests {
     fn test_collect_partial_seats_for_shard_errors() {
         let config = Config::new_mock(true);
         let (_, validators) =
-            parse_raw_validator_data(&new_test_raw_validator_data(), config.stake_per_seat);
-        let partial_seats = new_ordered_partial_seats(&validators, config.stake_per_seat);
+            parse_raw_validator_data(&new_test_raw_validator_data(), config.stake_per_seat.get())
+                .unwrap();
+        let partial_seats = new_ordered_partial_seats(&validators, config.stake_per_seat.get());
 
         insta::assert_debug_snapshot!(
             config.collect_partial_seats_for_shard(config.num_shards.into(), &partial_seats)
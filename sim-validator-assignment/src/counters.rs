@@ -0,0 +1,241 @@
+use num_rational::Ratio;
+use serde::{Deserialize, Serialize};
+
+use crate::histogram::Histogram;
+use crate::shard::ShardStatus;
+use crate::stats::{ConfidenceInterval, Z_95};
+
+/// Counts the corruption events observed while simulating blocks.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Counters {
+    pub num_blocks: u64,
+    pub num_shards: u64,
+    /// The corruption events for each threshold of malicious stake, in the order of
+    /// [`Config::max_malicious_stake_per_shard`](crate::config::Config::max_malicious_stake_per_shard).
+    pub thresholds: Vec<ThresholdCounters>,
+    /// The number of shards with each [`ShardStatus`].
+    pub shard_statuses: ShardStatusCounters,
+    /// The histogram of the malicious ratio across all shards, if it is collected.
+    pub malicious_ratio_histogram: Option<Histogram>,
+}
+
+/// Counts the shards and blocks which are corrupted with respect to one threshold.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct ThresholdCounters {
+    pub max_malicious_stake_per_shard: Ratio<u128>,
+    pub num_corrupted_shards: u64,
+    /// The number of blocks in which at least one shard is corrupted.
+    pub num_corrupted_blocks: u64,
+    /// The `k`th element holds the number of blocks with exactly `k` corrupted shards, for `k` in
+    /// `0..=num_shards_per_block`.
+    pub corrupted_shards_per_block: Vec<u64>,
+}
+
+/// Counts shards by their [`ShardStatus`].
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct ShardStatusCounters {
+    pub healthy: u64,
+    pub liveness_compromised: u64,
+    pub safety_compromised: u64,
+}
+
+impl ShardStatusCounters {
+    pub fn add(&mut self, status: ShardStatus) {
+        match status {
+            ShardStatus::Healthy => self.healthy += 1,
+            ShardStatus::LivenessCompromised => self.liveness_compromised += 1,
+            ShardStatus::SafetyCompromised => self.safety_compromised += 1,
+        }
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        self.healthy += other.healthy;
+        self.liveness_compromised += other.liveness_compromised;
+        self.safety_compromised += other.safety_compromised;
+    }
+}
+
+/// An estimated probability along with its 95% confidence interval.
+#[derive(Serialize, PartialEq, Debug, Clone, Copy)]
+pub struct ProbabilityEstimate {
+    pub estimate: f64,
+    pub confidence_interval: ConfidenceInterval,
+    pub relative_error: f64,
+}
+
+impl ProbabilityEstimate {
+    /// Estimates the probability of an event observed `successes` times in `trials`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `trials` is 0.
+    pub fn new(successes: u64, trials: u64) -> Self {
+        let estimate = successes as f64 / trials as f64;
+        let confidence_interval = ConfidenceInterval::wilson(successes, trials, Z_95);
+        Self {
+            estimate,
+            confidence_interval,
+            relative_error: confidence_interval.relative_error(estimate),
+        }
+    }
+}
+
+impl Counters {
+    /// Constructs counters for blocks with `num_shards_per_block` shards each, counting corruption
+    /// with respect to every threshold in `max_malicious_stake_per_shard`. A histogram of
+    /// the malicious ratio is collected if `histogram_buckets` is provided.
+    pub fn new(
+        num_shards_per_block: u16,
+        max_malicious_stake_per_shard: &[Ratio<u128>],
+        histogram_buckets: Option<u16>,
+    ) -> Self {
+        Self {
+            num_blocks: 0,
+            num_shards: 0,
+            thresholds: max_malicious_stake_per_shard
+                .iter()
+                .map(|&threshold| ThresholdCounters {
+                    max_malicious_stake_per_shard: threshold,
+                    num_corrupted_shards: 0,
+                    num_corrupted_blocks: 0,
+                    corrupted_shards_per_block: vec![0; usize::from(num_shards_per_block) + 1],
+                })
+                .collect(),
+            shard_statuses: ShardStatusCounters::default(),
+            malicious_ratio_histogram: histogram_buckets.map(Histogram::new),
+        }
+    }
+
+    /// Records a simulated block. The `i`th element of `num_corrupted_shards` holds the number of
+    /// shards corrupted with respect to the `i`th threshold.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an element of `num_corrupted_shards` exceeds the number of shards per block.
+    pub fn add_block(&mut self, num_shards_per_block: u16, num_corrupted_shards: &[u16]) {
+        self.num_blocks += 1;
+        self.num_shards += u64::from(num_shards_per_block);
+        for (threshold, &num_corrupted) in self.thresholds.iter_mut().zip(num_corrupted_shards) {
+            threshold.num_corrupted_shards += u64::from(num_corrupted);
+            if num_corrupted > 0 {
+                threshold.num_corrupted_blocks += 1;
+            }
+            threshold.corrupted_shards_per_block[usize::from(num_corrupted)] += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        self.num_blocks += other.num_blocks;
+        self.num_shards += other.num_shards;
+        for (threshold, other_threshold) in self.thresholds.iter_mut().zip(other.thresholds.iter())
+        {
+            threshold.num_corrupted_shards += other_threshold.num_corrupted_shards;
+            threshold.num_corrupted_blocks += other_threshold.num_corrupted_blocks;
+            for (count, other_count) in threshold
+                .corrupted_shards_per_block
+                .iter_mut()
+                .zip(other_threshold.corrupted_shards_per_block.iter())
+            {
+                *count += other_count;
+            }
+        }
+        self.shard_statuses.merge(&other.shard_statuses);
+        if let (Some(histogram), Some(other_histogram)) = (
+            self.malicious_ratio_histogram.as_mut(),
+            other.malicious_ratio_histogram.as_ref(),
+        ) {
+            histogram.merge(other_histogram);
+        }
+    }
+
+    /// Estimates the probability that a particular shard is corrupted with respect to the
+    /// `threshold_idx`th threshold.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no blocks were recorded.
+    pub fn shard_corruption(&self, threshold_idx: usize) -> ProbabilityEstimate {
+        ProbabilityEstimate::new(
+            self.thresholds[threshold_idx].num_corrupted_shards,
+            self.num_shards,
+        )
+    }
+
+    /// Estimates the probability that at least one shard of a block is corrupted with respect to
+    /// the `threshold_idx`th threshold.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no blocks were recorded.
+    pub fn block_corruption(&self, threshold_idx: usize) -> ProbabilityEstimate {
+        ProbabilityEstimate::new(
+            self.thresholds[threshold_idx].num_corrupted_blocks,
+            self.num_blocks,
+        )
+    }
+
+    /// Estimates the probability that a particular shard's liveness is compromised, which is also
+    /// the case if its safety is compromised.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no blocks were recorded.
+    pub fn liveness_failure(&self) -> ProbabilityEstimate {
+        ProbabilityEstimate::new(
+            self.shard_statuses.liveness_compromised + self.shard_statuses.safety_compromised,
+            self.num_shards,
+        )
+    }
+
+    /// Estimates the probability that a particular shard's safety is compromised.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no blocks were recorded.
+    pub fn safety_failure(&self) -> ProbabilityEstimate {
+        ProbabilityEstimate::new(self.shard_statuses.safety_compromised, self.num_shards)
+    }
+
+    /// Returns the largest relative error of the estimated probabilities of shard corruption
+    /// across all thresholds.
+    pub fn max_relative_error(&self) -> f64 {
+        (0..self.thresholds.len())
+            .map(|threshold_idx| self.shard_corruption(threshold_idx).relative_error)
+            .fold(0.0, f64::max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num_rational::Ratio;
+
+    use super::Counters;
+    use crate::shard::ShardStatus;
+
+    #[test]
+    fn test_counters() {
+        let thresholds = [Ratio::new(1, 3), Ratio::new(2, 3)];
+        let mut counters = Counters::new(3, &thresholds, None);
+        counters.add_block(3, &[0, 0]);
+        counters.add_block(3, &[2, 1]);
+
+        let mut other = Counters::new(3, &thresholds, None);
+        other.add_block(3, &[1, 0]);
+        other.add_block(3, &[2, 0]);
+        for status in [
+            ShardStatus::Healthy,
+            ShardStatus::LivenessCompromised,
+            ShardStatus::SafetyCompromised,
+        ] {
+            other.shard_statuses.add(status);
+        }
+        counters.merge(&other);
+
+        insta::assert_yaml_snapshot!(counters);
+        assert_eq!(counters.shard_corruption(0).estimate, 5.0 / 12.0);
+        assert_eq!(counters.block_corruption(0).estimate, 3.0 / 4.0);
+        assert_eq!(counters.block_corruption(1).estimate, 1.0 / 4.0);
+        assert_eq!(counters.liveness_failure().estimate, 2.0 / 12.0);
+        assert_eq!(counters.safety_failure().estimate, 1.0 / 12.0);
+    }
+}
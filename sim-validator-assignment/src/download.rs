@@ -7,7 +7,7 @@ use dl_validator_data::{NearProtocol, Protocol as DlValidatorDataProtocol};
 use crate::validator::RawValidatorData;
 
 #[derive(Args, Debug)]
-pub(crate) struct DownloadConfig {
+pub struct DownloadConfig {
     /// The protocol for which to download data.
     #[arg(long, value_enum)]
     pub protocol: Protocol,
@@ -24,7 +24,7 @@ pub(crate) struct DownloadConfig {
 }
 
 #[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
-pub(crate) enum Protocol {
+pub enum Protocol {
     Near,
 }
 
@@ -33,7 +33,7 @@ pub(crate) enum Protocol {
 ///
 /// Pretty print JSON assuming users might want to inspect and modify validator data (mark
 /// validators as malicious for simulations).
-pub(crate) fn download(config: &DownloadConfig) -> anyhow::Result<()> {
+pub fn download(config: &DownloadConfig) -> anyhow::Result<()> {
     // Download validator data.
     let protocol = match config.protocol {
         Protocol::Near => NearProtocol::new(config.rpc_url.clone(), config.block_height),
@@ -0,0 +1,388 @@
+use clap::Args;
+use num_rational::Ratio;
+use num_traits::ToPrimitive;
+use serde::Serialize;
+use std::path::PathBuf;
+
+use crate::output::{print_table, TableRow};
+use crate::rng::derive_seed;
+use crate::run::{log_seed, mock_validator_data, prepare_population, seed_or_random};
+use crate::seat_price::StakePerSeat;
+use crate::sweep::{simulate_run_config, SimulationArgs, ThresholdEstimates};
+use crate::validator::{read_validator_data, RawValidatorData};
+
+/// The domain of the seed changing validators at epoch boundaries, see [`derive_seed`].
+const SEED_DOMAIN: &[u8; 8] = b"evolving";
+
+#[derive(Args, Debug)]
+pub struct EpochsConfig {
+    /// The number of epochs to simulate. The first epoch uses the validator data as it is.
+    #[arg(long)]
+    pub num_epochs: u64,
+    /// The number of blocks to simulate per epoch.
+    #[arg(long)]
+    pub blocks_per_epoch: u64,
+    #[arg(long)]
+    pub num_shards: u16,
+    #[arg(long)]
+    pub seats_per_shard: u64,
+    /// The amount of stake required to get one seat. Pass `auto` to compute it from the validator
+    /// data of every epoch such that validators cover `num_shards * seats_per_shard` seats.
+    #[arg(long)]
+    pub stake_per_seat: StakePerSeat,
+    /// The thresholds of malicious stake above which a shard is considered corrupted, separated by
+    /// commas.
+    #[arg(long, value_delimiter = ',', required = true)]
+    pub max_malicious_stake_per_shard: Vec<Ratio<u128>>,
+    /// The file from which validator data of the first epoch is read. It is expected to contain a
+    /// vector of `RawValidatorData` serialized as JSON. If no validator data is provided, mocked
+    /// validator data will be used.
+    #[arg(long)]
+    pub validator_data: Option<PathBuf>,
+    /// At every epoch boundary, the stake of each validator is multiplied by a factor drawn
+    /// uniformly from `[1 - stake_drift, 1 + stake_drift]`.
+    #[arg(long, default_value_t = 0.0)]
+    pub stake_drift: f64,
+    /// At every epoch boundary, the stake of each malicious validator additionally grows by this
+    /// fraction, e.g. `0.01` for 1% per epoch.
+    #[arg(long, default_value_t = 0.0)]
+    pub malicious_growth: f64,
+    /// The probability that a validator leaves at an epoch boundary.
+    #[arg(long, default_value_t = 0.0)]
+    pub leave_probability: f64,
+    /// The number of validators joining at every epoch boundary. The stake of a joining validator
+    /// is copied from a random validator of the previous epoch.
+    #[arg(long, default_value_t = 0)]
+    pub joins_per_epoch: u64,
+    /// The probability that a joining validator is malicious.
+    #[arg(long, default_value_t = 0.0)]
+    pub join_malicious_probability: f64,
+    #[command(flatten)]
+    pub args: SimulationArgs,
+}
+
+impl EpochsConfig {
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.num_epochs == 0 {
+            anyhow::bail!("at least one epoch is required");
+        }
+        for (name, probability) in [
+            ("stake_drift", self.stake_drift),
+            ("leave_probability", self.leave_probability),
+            (
+                "join_malicious_probability",
+                self.join_malicious_probability,
+            ),
+        ] {
+            if !(0.0..=1.0).contains(&probability) {
+                anyhow::bail!("{name} must be in [0, 1], got {probability}");
+            }
+        }
+        if !(self.malicious_growth >= 0.0 && self.malicious_growth.is_finite()) {
+            anyhow::bail!(
+                "malicious_growth must be non-negative, got {}",
+                self.malicious_growth
+            );
+        }
+        Ok(())
+    }
+}
+
+/// A row of the result table, holding the estimates of one epoch and threshold.
+#[derive(Serialize, Debug)]
+pub struct EpochRow {
+    pub epoch: u64,
+    pub num_validators: usize,
+    pub stake_per_seat: u128,
+    pub malicious_stake_ratio: f64,
+    pub seats: u64,
+    pub malicious_seats: u64,
+    #[serde(flatten)]
+    pub estimates: ThresholdEstimates,
+}
+
+impl TableRow for EpochRow {
+    fn header() -> String {
+        format!(
+            "epoch\tnum_validators\tstake_per_seat\tmalicious_stake/stake\tmalicious_seats/seats\t{}",
+            ThresholdEstimates::HEADER
+        )
+    }
+
+    fn cells(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{:.5}\t{}/{}\t{}",
+            self.epoch,
+            self.num_validators,
+            self.stake_per_seat,
+            self.malicious_stake_ratio,
+            self.malicious_seats,
+            self.seats,
+            self.estimates.cells()
+        )
+    }
+}
+
+/// Simulates `config.num_epochs` epochs, changing the set of validators at every epoch boundary,
+/// and prints the probabilities of corruption per epoch.
+///
+/// Validators are parsed again in every epoch, so seats (and with `auto` the stake per seat)
+/// reflect the changed stakes. The changes of validators are drawn with a seed derived from
+/// `--seed`, independently of the shuffles of seats.
+pub fn epochs(config: &EpochsConfig) -> anyhow::Result<()> {
+    let raw_validator_data = match &config.validator_data {
+        Some(file_path) => read_validator_data(file_path.as_path())?,
+        None => mock_validator_data(),
+    };
+    let seed = seed_or_random(config.args.seed);
+    let rows = simulate_epochs(config, raw_validator_data, seed)?;
+
+    print_table(&rows);
+    config.args.write_table(&rows)?;
+    log_seed(seed, config.args.threads);
+    Ok(())
+}
+
+/// Returns the rows of the result table for validators starting as `raw_validator_data`,
+/// simulating every epoch with rngs seeded by a seed derived from `seed` and the epoch.
+pub fn simulate_epochs(
+    config: &EpochsConfig,
+    mut raw_validator_data: Vec<RawValidatorData>,
+    seed: u64,
+) -> anyhow::Result<Vec<EpochRow>> {
+    config.validate()?;
+    let mut rng = fastrand::Rng::with_seed(derive_seed(seed, SEED_DOMAIN));
+    let mut num_joined = 0;
+    let run_config = config.args.run_config(
+        config.blocks_per_epoch,
+        config.num_shards,
+        config.seats_per_shard,
+        config.stake_per_seat,
+        config.max_malicious_stake_per_shard.clone(),
+    );
+
+    let mut rows = vec![];
+    for epoch in 0..config.num_epochs {
+        if epoch > 0 {
+            evolve_validators(&mut raw_validator_data, config, &mut rng, &mut num_joined)
+                .map_err(|err| anyhow::anyhow!("epoch {epoch}: {err}"))?;
+        }
+
+        let (epoch_config, population_stats, population) =
+            prepare_population(&run_config, &raw_validator_data)
+                .map_err(|err| anyhow::anyhow!("epoch {epoch}: {err}"))?;
+        // Every epoch is a domain of its own, distinct from `SEED_DOMAIN` for realistic epochs.
+        let epoch_seed = derive_seed(seed, &epoch.to_be_bytes());
+        let counters = simulate_run_config(&epoch_config, &population, epoch_seed)?;
+        let malicious_stake_ratio =
+            Ratio::new(population_stats.malicious_stake, population_stats.stake)
+                .to_f64()
+                .unwrap();
+        println!(
+            "Simulated epoch {epoch}: {} validators, malicious_stake / stake ≈ {malicious_stake_ratio:.5}",
+            raw_validator_data.len()
+        );
+
+        rows.extend(
+            ThresholdEstimates::from_counters(&counters)
+                .into_iter()
+                .map(|estimates| EpochRow {
+                    epoch,
+                    num_validators: raw_validator_data.len(),
+                    stake_per_seat: epoch_config.stake_per_seat.get(),
+                    malicious_stake_ratio,
+                    seats: population_stats.seats,
+                    malicious_seats: population_stats.malicious_seats,
+                    estimates,
+                }),
+        );
+    }
+    Ok(rows)
+}
+
+/// Applies the changes of an epoch boundary to `raw_validator_data`: validators leave, stakes
+/// drift and malicious stake grows, then new validators join. `num_joined` counts the validators
+/// that joined so far and is used to name joining validators, skipping names which are taken.
+fn evolve_validators(
+    raw_validator_data: &mut Vec<RawValidatorData>,
+    config: &EpochsConfig,
+    rng: &mut fastrand::Rng,
+    num_joined: &mut u64,
+) -> anyhow::Result<()> {
+    let stakes: Vec<u128> = raw_validator_data.iter().map(|v| v.stake).collect();
+    if stakes.is_empty() {
+        anyhow::bail!("no validators are left");
+    }
+
+    raw_validator_data.retain(|_| rng.f64() >= config.leave_probability);
+    if raw_validator_data.is_empty() && config.joins_per_epoch == 0 {
+        anyhow::bail!("all validators left and none join");
+    }
+    for v in raw_validator_data.iter_mut() {
+        let mut factor = 1.0 + config.stake_drift * (2.0 * rng.f64() - 1.0);
+        if v.is_malicious {
+            factor *= 1.0 + config.malicious_growth;
+        }
+        // Converting to `f64` loses precision for large stakes, so unchanged stakes are kept as
+        // they are. The cast back saturates, hence stake does not overflow.
+        if factor != 1.0 {
+            v.stake = (v.stake as f64 * factor).round() as u128;
+        }
+    }
+
+    for _ in 0..config.joins_per_epoch {
+        let account_id = loop {
+            let account_id = format!("joined_{num_joined}");
+            *num_joined += 1;
+            if raw_validator_data
+                .iter()
+                .all(|v| v.account_id != account_id)
+            {
+                break account_id;
+            }
+        };
+        raw_validator_data.push(RawValidatorData {
+            account_id,
+            stake: stakes[rng.usize(..stakes.len())],
+            is_malicious: rng.f64() < config.join_malicious_probability,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use num_rational::Ratio;
+
+    use super::{evolve_validators, simulate_epochs, EpochsConfig};
+    use crate::seat_price::StakePerSeat;
+    use crate::sweep::SimulationArgs;
+    use crate::validator::tests::new_test_raw_validator_data;
+
+    fn new_test_config() -> EpochsConfig {
+        EpochsConfig {
+            num_epochs: 2,
+            blocks_per_epoch: 10,
+            num_shards: 4,
+            seats_per_shard: 2,
+            stake_per_seat: StakePerSeat::Fixed(100),
+            max_malicious_stake_per_shard: vec![Ratio::new(1, 3)],
+            validator_data: None,
+            stake_drift: 0.0,
+            malicious_growth: 0.0,
+            leave_probability: 0.0,
+            joins_per_epoch: 0,
+            join_malicious_probability: 0.0,
+            args: SimulationArgs::default_for_test(),
+        }
+    }
+
+    #[test]
+    fn test_evolve_validators() {
+        let config = EpochsConfig {
+            malicious_growth: 0.5,
+            joins_per_epoch: 2,
+            ..new_test_config()
+        };
+        let mut raw_validator_data = new_test_raw_validator_data();
+        let mut num_joined = 0;
+        evolve_validators(
+            &mut raw_validator_data,
+            &config,
+            &mut fastrand::Rng::with_seed(0),
+            &mut num_joined,
+        )
+        .unwrap();
+
+        assert_eq!(num_joined, 2);
+        assert_eq!(raw_validator_data.len(), 14);
+        // Without drift only the stakes of malicious validators change.
+        assert_eq!(raw_validator_data[0].stake, 500);
+        assert_eq!(raw_validator_data[1].stake, 465);
+        assert_eq!(raw_validator_data[3].stake, 150);
+        assert_eq!(raw_validator_data[12].account_id, "joined_0");
+        assert!(!raw_validator_data[12].is_malicious);
+
+        // Names of joining validators which are taken are skipped.
+        raw_validator_data[0].account_id = "joined_2".to_owned();
+        let config = EpochsConfig {
+            joins_per_epoch: 1,
+            ..new_test_config()
+        };
+        evolve_validators(
+            &mut raw_validator_data,
+            &config,
+            &mut fastrand::Rng::with_seed(0),
+            &mut num_joined,
+        )
+        .unwrap();
+        assert_eq!(raw_validator_data[14].account_id, "joined_3");
+        assert_eq!(num_joined, 4);
+
+        let config = EpochsConfig {
+            leave_probability: 1.0,
+            ..new_test_config()
+        };
+        assert!(evolve_validators(
+            &mut raw_validator_data,
+            &config,
+            &mut fastrand::Rng::with_seed(0),
+            &mut num_joined,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_simulate_epochs() {
+        // Malicious stake doubles every epoch, which raises the stake per seat computed for the 8
+        // seats. It is 100 for the test validators.
+        let config = EpochsConfig {
+            num_epochs: 3,
+            stake_per_seat: StakePerSeat::Auto,
+            malicious_growth: 1.0,
+            joins_per_epoch: 1,
+            ..new_test_config()
+        };
+        let rows = simulate_epochs(&config, new_test_raw_validator_data(), 42).unwrap();
+
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].stake_per_seat, 100);
+        for (epoch, row) in (0..).zip(rows.iter()) {
+            assert_eq!(row.epoch, epoch);
+            assert_eq!(row.num_validators, 12 + epoch as usize);
+            assert!(row.seats >= 8);
+        }
+        assert!(rows[1].stake_per_seat > rows[0].stake_per_seat);
+        assert!(rows[2].stake_per_seat > rows[1].stake_per_seat);
+        assert!(rows[2].malicious_stake_ratio > rows[0].malicious_stake_ratio);
+
+        // A fixed seed reproduces the simulation.
+        let reproduced = simulate_epochs(&config, new_test_raw_validator_data(), 42).unwrap();
+        assert_eq!(
+            serde_json::to_value(&rows).unwrap(),
+            serde_json::to_value(&reproduced).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(new_test_config().validate().is_ok());
+        let invalid_configs = [
+            EpochsConfig {
+                num_epochs: 0,
+                ..new_test_config()
+            },
+            EpochsConfig {
+                stake_drift: 1.5,
+                ..new_test_config()
+            },
+            EpochsConfig {
+                malicious_growth: -0.1,
+                ..new_test_config()
+            },
+        ];
+        for config in invalid_configs.iter() {
+            assert!(config.validate().is_err());
+        }
+    }
+}
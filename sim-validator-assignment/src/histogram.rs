@@ -0,0 +1,116 @@
+use num_rational::Ratio;
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use crate::output::OutputFormat;
+
+/// A histogram of ratios in `[0, 1]` with equal-width buckets.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Histogram {
+    /// The `i`th element holds the number of ratios in `[i / n, (i + 1) / n)`, with `n` the number
+    /// of buckets. The last bucket is closed and also holds ratios equal to 1.
+    counts: Vec<u64>,
+}
+
+/// A bucket of a [`Histogram`], as written to files.
+#[derive(Serialize, PartialEq, Debug)]
+pub struct Bucket {
+    pub lower: f64,
+    pub upper: f64,
+    pub count: u64,
+}
+
+impl Histogram {
+    /// Constructs an empty histogram.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `num_buckets` is 0.
+    pub fn new(num_buckets: u16) -> Self {
+        assert!(num_buckets > 0, "histogram requires at least one bucket");
+        Self {
+            counts: vec![0; usize::from(num_buckets)],
+        }
+    }
+
+    /// Adds `ratio` to the bucket covering it.
+    pub fn add(&mut self, ratio: Ratio<u128>) {
+        let idx = self.bucket_idx(ratio);
+        self.counts[idx] += 1;
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        for (count, other_count) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *count += other_count;
+        }
+    }
+
+    pub fn buckets(&self) -> Vec<Bucket> {
+        let num_buckets = self.counts.len() as f64;
+        (0u32..)
+            .zip(self.counts.iter())
+            .map(|(idx, &count)| Bucket {
+                lower: f64::from(idx) / num_buckets,
+                upper: f64::from(idx + 1) / num_buckets,
+                count,
+            })
+            .collect()
+    }
+
+    /// Writes the buckets of the histogram to `path`.
+    pub fn write(&self, path: &Path, format: OutputFormat) -> anyhow::Result<()> {
+        let content = match format {
+            OutputFormat::Json => serde_json::to_string_pretty(&self.buckets())?,
+            OutputFormat::Csv => {
+                let mut csv = String::from("lower,upper,count\n");
+                for bucket in self.buckets() {
+                    csv.push_str(&format!(
+                        "{},{},{}\n",
+                        bucket.lower, bucket.upper, bucket.count
+                    ));
+                }
+                csv
+            }
+        };
+        let mut file = File::create(path)?;
+        file.write_all(content.as_bytes())?;
+        Ok(())
+    }
+
+    fn bucket_idx(&self, ratio: Ratio<u128>) -> usize {
+        let num_buckets = self.counts.len();
+        let scaled = u128::try_from(num_buckets)
+            .ok()
+            .and_then(|n| ratio.numer().checked_mul(n))
+            .map(|numer| numer / ratio.denom())
+            // Fall back to floating point arithmetic only if the exact computation overflows.
+            .unwrap_or_else(|| (ratio.to_f64().unwrap() * num_buckets as f64) as u128);
+        usize::try_from(scaled)
+            .unwrap_or(usize::MAX)
+            .min(num_buckets - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Histogram;
+    use num_rational::Ratio;
+
+    #[test]
+    fn test_histogram() {
+        let mut histogram = Histogram::new(4);
+        histogram.add(Ratio::new(0, 10));
+        histogram.add(Ratio::new(1, 4));
+        histogram.add(Ratio::new(7, 10));
+
+        let mut other = Histogram::new(4);
+        other.add(Ratio::new(1, 1));
+        other.add(Ratio::new(u128::MAX - 1, u128::MAX));
+        histogram.merge(&other);
+
+        insta::assert_yaml_snapshot!(histogram.buckets());
+    }
+}
@@ -0,0 +1,466 @@
+use num_rational::Ratio;
+
+use crate::analyze::{ln_add, SeatModel};
+use crate::compact::{CompactPopulation, ValidatorSet};
+use crate::config::Config;
+use crate::counters::ProbabilityEstimate;
+use crate::rng::{Shuffle, SimRng};
+use crate::run::simulate_batch;
+use crate::run_result::ThresholdResult;
+use crate::shard::Shard;
+use crate::stats::{ConfidenceInterval, Z_95};
+use crate::validator::PopulationStats;
+
+/// The number of samples drawn between two heartbeats.
+const HEARTBEAT_INTERVAL: u64 = 100_000;
+
+/// Estimates the probability that a particular shard is corrupted with importance sampling and
+/// prints the estimate for each threshold of malicious stake. For every threshold, one sample is
+/// drawn per block in `config.num_blocks`. Returns the number of samples drawn per threshold and
+/// the estimates.
+///
+/// A plain simulation hardly ever observes a corrupted shard if the probability of corruption is
+/// tiny. Instead, the number of malicious seats in the shard is drawn from a [`TiltedProposal`]
+/// that favors malicious-heavy shards. Each sample is reweighted by its likelihood ratio, hence
+/// the estimate stays unbiased. Partial seats are assigned as in a plain simulation.
+///
+/// Every threshold is sampled separately from a proposal targeting its own corruption. A proposal
+/// tilted towards a higher threshold yields estimates for lower thresholds whose variance is so
+/// large that their confidence intervals are meaningless.
+pub fn run_importance_sampling(
+    config: &Config,
+    population_stats: &PopulationStats,
+    population: &CompactPopulation,
+    rngs: &mut [SimRng],
+) -> anyhow::Result<(u64, Vec<ThresholdResult>)> {
+    let proposals = config
+        .max_malicious_stake_per_shard
+        .iter()
+        .map(|&threshold| {
+            let proposal = new_proposal(config, population_stats, threshold)?;
+            println!(
+                "importance sampling of {threshold}: tilt {:.5}, target of {} malicious seats per shard",
+                proposal.tilt, proposal.target_malicious_seats
+            );
+            Ok(proposal)
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let mut estimates: Vec<Estimate> = config
+        .max_malicious_stake_per_shard
+        .iter()
+        .map(|_| Estimate::default())
+        .collect();
+    let sample_limit = config.block_limit();
+    let mut num_samples = 0;
+    while num_samples < sample_limit {
+        let num_batch_samples = HEARTBEAT_INTERVAL.min(sample_limit - num_samples);
+        for worker_estimates in simulate_batch(rngs, num_batch_samples, |rng, num_samples| {
+            sample_shards(config, population, &proposals, rng, num_samples)
+        })? {
+            for (estimate, worker_estimate) in estimates.iter_mut().zip(worker_estimates.iter()) {
+                estimate.merge(worker_estimate);
+            }
+        }
+        num_samples += num_batch_samples;
+
+        let thresholds = config
+            .max_malicious_stake_per_shard
+            .iter()
+            .zip(estimates.iter())
+            .map(|(threshold, estimate)| {
+                format!("{threshold}: p(shard corrupted) ≈ {:.4e}", estimate.mean())
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!("heartbeat(samples: {num_samples}): {thresholds}");
+        let max_relative_error = estimates
+            .iter()
+            .map(|estimate| {
+                estimate
+                    .confidence_interval()
+                    .relative_error(estimate.mean())
+            })
+            .fold(0.0, f64::max);
+        if config.is_precise_enough(max_relative_error) {
+            println!("Reached the target relative error after {num_samples} samples.");
+            break;
+        }
+    }
+
+    println!("Drew {num_samples} samples per threshold.");
+    println!("max_malicious_stake_per_shard\tcorrupted_samples\tp(shard corrupted)\tvariance\tstandard error\trelative error\t95% confidence interval\tunion bound of p(any shard in block corrupted)");
+    for (threshold, estimate) in config
+        .max_malicious_stake_per_shard
+        .iter()
+        .zip(estimates.iter())
+    {
+        let std_error = estimate.std_error();
+        let interval = estimate.confidence_interval();
+        println!(
+            "{threshold}\t{}\t{:.4e}\t{:.4e}\t{:.4e}\t{:.5}\t[{:.4e}, {:.4e}]\t{:.4e}",
+            estimate.num_corrupted,
+            estimate.mean(),
+            estimate.variance(),
+            std_error,
+            std_error / estimate.mean(),
+            interval.lower,
+            interval.upper,
+            (estimate.mean() * f64::from(config.num_shards)).min(1.0)
+        );
+    }
+
+    let results = config
+        .max_malicious_stake_per_shard
+        .iter()
+        .zip(estimates.iter())
+        .map(|(&threshold, estimate)| ThresholdResult {
+            max_malicious_stake_per_shard: threshold,
+            num_corrupted_shards: estimate.num_corrupted,
+            shard_corruption: estimate.probability_estimate(),
+            block_corruption: None,
+        })
+        .collect();
+    Ok((num_samples, results))
+}
+
+/// Returns the proposal targeting the corruption of a shard with respect to `threshold`.
+fn new_proposal(
+    config: &Config,
+    population_stats: &PopulationStats,
+    threshold: Ratio<u128>,
+) -> anyhow::Result<TiltedProposal> {
+    let model = SeatModel::new(
+        population_stats,
+        config.num_shards,
+        config.seats_per_shard,
+        threshold,
+    )?;
+    // The smallest number of malicious seats which corrupts a shard without partial seats.
+    let target_malicious_seats = model.get_max_malicious_seats_per_shard() + 1;
+    Ok(TiltedProposal::new(
+        &model.ln_shard_pmf(),
+        target_malicious_seats,
+    ))
+}
+
+/// Draws `num_samples` shards per threshold in `config.max_malicious_stake_per_shard`, each from
+/// the corresponding element of `proposals`, and returns the estimates of the probability of shard
+/// corruption, one for each threshold.
+fn sample_shards(
+    config: &Config,
+    population: &CompactPopulation,
+    proposals: &[TiltedProposal],
+    rng: &mut SimRng,
+    num_samples: u64,
+) -> anyhow::Result<Vec<Estimate>> {
+    let seats = population.new_ordered_seats();
+    let (mut malicious_seats, mut honest_seats): (Vec<u32>, Vec<u32>) = seats
+        .iter()
+        .partition(|&&validator_idx| population.is_malicious(validator_idx));
+    let mut partial_seats = if config.include_partial_seats {
+        population.new_ordered_partial_seats()
+    } else {
+        Vec::new()
+    };
+    let seats_per_shard = usize::try_from(config.seats_per_shard).unwrap();
+    let mut shard_seats = Vec::with_capacity(seats_per_shard);
+    let mut distinct_validators = ValidatorSet::new(population);
+    let mut estimates = vec![];
+
+    for (&threshold, proposal) in config
+        .max_malicious_stake_per_shard
+        .iter()
+        .zip(proposals.iter())
+    {
+        let mut estimate = Estimate::default();
+        for _ in 0..num_samples {
+            let num_malicious = proposal.sample(rng);
+            shard_seats.clear();
+            shard_seats.extend_from_slice(choose(&mut malicious_seats, num_malicious, rng));
+            shard_seats.extend_from_slice(choose(
+                &mut honest_seats,
+                seats_per_shard - num_malicious,
+                rng,
+            ));
+            rng.shuffle(&mut partial_seats);
+
+            let shard = Shard::from_compact(
+                config,
+                population,
+                &shard_seats,
+                config.collect_partial_seats_for_shard(0, &partial_seats)?,
+                &mut distinct_validators,
+            )?;
+            estimate.add(if shard.is_corrupted(threshold) {
+                proposal.ln_weight(num_malicious).exp()
+            } else {
+                0.0
+            });
+        }
+        estimates.push(estimate);
+    }
+
+    Ok(estimates)
+}
+
+/// Moves `num` uniformly chosen elements of `items` to its front and returns them.
+///
+/// # Panics
+///
+/// Panics if `num > items.len()`.
+fn choose<'items>(items: &'items mut [u32], num: usize, rng: &mut impl Shuffle) -> &'items [u32] {
+    for i in 0..num {
+        let j = i + rng.next_index(items.len() - i);
+        items.swap(i, j);
+    }
+    &items[..num]
+}
+
+/// An exponentially tilted version of the distribution of malicious seats per shard.
+///
+/// For the distribution `p(m)` of the number `m` of malicious seats in a shard, the proposal is
+/// `q(m) = p(m) * exp(tilt * m) / Z` with normalization `Z`. A positive `tilt` shifts probability
+/// mass towards malicious-heavy shards.
+#[derive(Debug)]
+pub struct TiltedProposal {
+    tilt: f64,
+    /// The number of malicious seats the proposal's mean is tilted towards.
+    target_malicious_seats: u64,
+    /// The largest value with non-zero probability.
+    max_m: usize,
+    /// The `m`th element holds `ln(p(m) / q(m))`.
+    ln_weights: Vec<f64>,
+    /// The `m`th element holds `q(0) + ... + q(m)`.
+    cdf: Vec<f64>,
+}
+
+impl TiltedProposal {
+    /// Constructs the proposal for the distribution with logarithms `ln_pmf`. The tilt is chosen
+    /// such that the proposal's mean equals `target_malicious_seats`. If the mean of `ln_pmf`
+    /// already exceeds it, the distribution is not tilted.
+    pub fn new(ln_pmf: &[f64], target_malicious_seats: u64) -> Self {
+        let max_m = ln_pmf
+            .iter()
+            .rposition(|&ln_p| ln_p > f64::NEG_INFINITY)
+            .expect("pmf should have support");
+        // The mean of a tilted distribution cannot exceed the largest value in its support.
+        let target_mean = (target_malicious_seats as f64)
+            .min(max_m as f64 - 0.5)
+            .max(0.0);
+
+        let tilt = if Self::mean(ln_pmf, 0.0) >= target_mean {
+            0.0
+        } else {
+            let (mut low, mut high) = (0.0, 1.0);
+            while Self::mean(ln_pmf, high) < target_mean {
+                high *= 2.0;
+            }
+            for _ in 0..100 {
+                let mid = (low + high) / 2.0;
+                if Self::mean(ln_pmf, mid) < target_mean {
+                    low = mid;
+                } else {
+                    high = mid;
+                }
+            }
+            high
+        };
+
+        let ln_z = Self::ln_normalization(ln_pmf, tilt);
+        let ln_weights = (0..ln_pmf.len()).map(|m| ln_z - tilt * m as f64).collect();
+        let mut cdf = Vec::with_capacity(ln_pmf.len());
+        let mut sum = 0.0;
+        for (m, &ln_p) in ln_pmf.iter().enumerate() {
+            sum += (ln_p + tilt * m as f64 - ln_z).exp();
+            cdf.push(sum);
+        }
+
+        Self {
+            tilt,
+            target_malicious_seats,
+            max_m,
+            ln_weights,
+            cdf,
+        }
+    }
+
+    /// Draws a number of malicious seats from the proposal.
+    pub fn sample(&self, rng: &mut impl Shuffle) -> usize {
+        // A uniformly distributed value in `[0, 1)` built from the upper 53 bits.
+        let u = (rng.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+        let m = self.cdf.partition_point(|&c| c <= u);
+        // Rounding might let the last element of `cdf` be slightly less than 1.
+        m.min(self.max_m)
+    }
+
+    /// Returns the logarithm of the likelihood ratio `p(m) / q(m)`.
+    pub fn ln_weight(&self, m: usize) -> f64 {
+        self.ln_weights[m]
+    }
+
+    fn ln_normalization(ln_pmf: &[f64], tilt: f64) -> f64 {
+        ln_pmf
+            .iter()
+            .enumerate()
+            .fold(f64::NEG_INFINITY, |acc, (m, &ln_p)| {
+                ln_add(acc, ln_p + tilt * m as f64)
+            })
+    }
+
+    fn mean(ln_pmf: &[f64], tilt: f64) -> f64 {
+        let ln_z = Self::ln_normalization(ln_pmf, tilt);
+        ln_pmf
+            .iter()
+            .enumerate()
+            .map(|(m, &ln_p)| m as f64 * (ln_p + tilt * m as f64 - ln_z).exp())
+            .sum()
+    }
+}
+
+/// Accumulates weighted samples, where a sample's value is its weight if the shard is corrupted
+/// and 0 otherwise.
+#[derive(Default, Debug)]
+pub struct Estimate {
+    num_samples: u64,
+    num_corrupted: u64,
+    sum: f64,
+    sum_of_squares: f64,
+}
+
+impl Estimate {
+    pub fn add(&mut self, value: f64) {
+        self.num_samples += 1;
+        if value > 0.0 {
+            self.num_corrupted += 1;
+        }
+        self.sum += value;
+        self.sum_of_squares += value * value;
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        self.num_samples += other.num_samples;
+        self.num_corrupted += other.num_corrupted;
+        self.sum += other.sum;
+        self.sum_of_squares += other.sum_of_squares;
+    }
+
+    /// Returns the unbiased estimate of the probability of shard corruption.
+    pub fn mean(&self) -> f64 {
+        self.sum / self.num_samples as f64
+    }
+
+    /// Returns the variance of the estimate, i.e. the sample variance divided by the number of
+    /// samples.
+    pub fn variance(&self) -> f64 {
+        let n = self.num_samples as f64;
+        if self.num_samples < 2 {
+            return f64::NAN;
+        }
+        let sample_variance = (self.sum_of_squares - self.sum * self.sum / n) / (n - 1.0);
+        sample_variance.max(0.0) / n
+    }
+
+    pub fn std_error(&self) -> f64 {
+        self.variance().sqrt()
+    }
+
+    /// Returns the 95% confidence interval of the estimate based on the normal approximation,
+    /// which holds for a large number of samples.
+    pub fn confidence_interval(&self) -> ConfidenceInterval {
+        ConfidenceInterval::normal(self.mean(), self.std_error(), Z_95)
+    }
+
+    pub fn probability_estimate(&self) -> ProbabilityEstimate {
+        let confidence_interval = self.confidence_interval();
+        ProbabilityEstimate {
+            estimate: self.mean(),
+            confidence_interval,
+            relative_error: confidence_interval.relative_error(self.mean()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num_rational::Ratio;
+
+    use super::{new_proposal, sample_shards, TiltedProposal};
+    use crate::analyze::SeatModel;
+    use crate::compact::CompactPopulation;
+    use crate::config::Config;
+    use crate::mocks::new_validators;
+    use crate::rng::{RngBackend, SimRng};
+    use crate::seat_price::StakePerSeat;
+    use crate::validator::parse_raw_validator_data;
+
+    #[test]
+    fn test_tilted_proposal() {
+        let ln_pmf: Vec<f64> = [0.5, 0.3, 0.15, 0.05]
+            .iter()
+            .map(|p: &f64| p.ln())
+            .collect();
+        let proposal = TiltedProposal::new(&ln_pmf, 2);
+        assert!(proposal.tilt > 0.0);
+
+        // The proposal's probabilities reweighted by likelihood ratios sum to 1.
+        let mut previous = 0.0;
+        let mut sum = 0.0;
+        for (m, c) in proposal.cdf.iter().enumerate() {
+            sum += (c - previous) * proposal.ln_weight(m).exp();
+            previous = *c;
+        }
+        assert!((sum - 1.0).abs() < 1e-12);
+        assert!((previous - 1.0).abs() < 1e-12);
+
+        // No tilt if the target is below the mean.
+        assert_eq!(TiltedProposal::new(&ln_pmf, 0).tilt, 0.0);
+    }
+
+    #[test]
+    fn test_estimates_match_analytic_probabilities() {
+        let mut config = Config::new_mock(false);
+        config.num_shards = 2;
+        config.seats_per_shard = 20;
+        config.stake_per_seat = StakePerSeat::Fixed(1);
+        config.max_malicious_stake_per_shard = vec![Ratio::new(1, 4), Ratio::new(3, 4)];
+        let (population_stats, validators) =
+            parse_raw_validator_data(&new_validators(100, 1, 20), config.stake_per_seat.get())
+                .unwrap();
+        let population = CompactPopulation::new(&validators, config.stake_per_seat.get()).unwrap();
+        let proposals: Vec<TiltedProposal> = config
+            .max_malicious_stake_per_shard
+            .iter()
+            .map(|&threshold| new_proposal(&config, &population_stats, threshold).unwrap())
+            .collect();
+
+        let mut rng = SimRng::new(RngBackend::Chacha, 0, 0, None).unwrap();
+        let estimates = sample_shards(&config, &population, &proposals, &mut rng, 10_000).unwrap();
+
+        // The exact probability for 1/4 is large, the one for 3/4 is around 1e-8, which plain
+        // sampling would never observe. Both are estimated precisely.
+        for (&threshold, estimate) in config
+            .max_malicious_stake_per_shard
+            .iter()
+            .zip(estimates.iter())
+        {
+            let model = SeatModel::new(
+                &population_stats,
+                config.num_shards,
+                config.seats_per_shard,
+                threshold,
+            )
+            .unwrap();
+            let exact = model.ln_shard_corruption_probability().exp();
+            assert!(
+                (estimate.mean() - exact).abs() < 4.0 * estimate.std_error(),
+                "estimate {} for {threshold} with standard error {} deviates from {exact}",
+                estimate.mean(),
+                estimate.std_error()
+            );
+            assert!(estimate.std_error() / estimate.mean() < 0.05);
+        }
+        assert!(estimates[0].mean() > 0.1);
+        assert!(estimates[1].mean() < 1e-6);
+    }
+}
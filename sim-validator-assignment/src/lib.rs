@@ -0,0 +1,28 @@
+pub mod adaptive;
+pub mod adversary;
+pub mod analyze;
+pub mod checkpoint;
+pub mod compact;
+pub mod config;
+pub mod counters;
+pub mod download;
+pub mod epochs;
+pub mod histogram;
+pub mod importance_sampling;
+pub mod malicious;
+pub mod mocks;
+pub mod output;
+pub mod partial_seat;
+pub mod rng;
+pub mod run;
+pub mod run_result;
+pub mod scenario;
+pub mod seat;
+pub mod seat_price;
+pub mod seat_stats;
+pub mod shard;
+pub mod stats;
+pub mod sweep;
+pub mod tolerance;
+pub mod validation;
+pub mod validator;
@@ -1,18 +1,17 @@
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
 
-mod config;
-use config::Config;
-mod download;
-use download::{download, DownloadConfig};
-mod mocks;
-mod partial_seat;
-mod run;
-mod seat;
-mod seat_stats;
-use seat_stats::{print_seat_stats, SeatStatsConfig};
-mod shard;
-use run::run;
-mod validator;
+use sim_validator_assignment::adaptive::{adaptive, AdaptiveConfig};
+use sim_validator_assignment::analyze::{analyze, AnalyzeConfig};
+use sim_validator_assignment::config::Config;
+use sim_validator_assignment::download::{download, DownloadConfig};
+use sim_validator_assignment::epochs::{epochs, EpochsConfig};
+use sim_validator_assignment::malicious::{mark_malicious, MarkMaliciousConfig};
+use sim_validator_assignment::run::run;
+use sim_validator_assignment::scenario::apply_scenario;
+use sim_validator_assignment::seat_stats::{print_seat_stats, SeatStatsConfig};
+use sim_validator_assignment::sweep::{sweep, SweepConfig};
+use sim_validator_assignment::tolerance::{tolerance, ToleranceConfig};
+use sim_validator_assignment::validation::{validate_data, ValidateDataConfig};
 
 /// A CLI to simulate blockchain validator assignments.
 #[derive(Parser, Debug)]
@@ -27,18 +26,53 @@ struct Cli {
 enum Command {
     /// Runs a simulation
     #[command(arg_required_else_help = true)]
-    Run(Config),
+    Run(Box<Config>),
     /// Downloads valdiator data
     Download(DownloadConfig),
     /// Prints seat stats
     SeatStats(SeatStatsConfig),
+    /// Computes probabilities of shard corruption analytically, ignoring partial seats
+    #[command(arg_required_else_help = true)]
+    Analyze(AnalyzeConfig),
+    /// Runs simulations for every combination of ranges of parameters
+    #[command(arg_required_else_help = true)]
+    Sweep(SweepConfig),
+    /// Simulates epochs with changing stake and validator membership
+    #[command(arg_required_else_help = true)]
+    Epochs(EpochsConfig),
+    /// Simulates an adaptive adversary corrupting validators after seats are assigned
+    #[command(arg_required_else_help = true)]
+    Adaptive(AdaptiveConfig),
+    /// Simulates increasing fractions of malicious stake to find the fraction that is tolerated
+    #[command(arg_required_else_help = true)]
+    Tolerance(ToleranceConfig),
+    /// Marks validators as malicious according to a rule
+    #[command(arg_required_else_help = true)]
+    MarkMalicious(MarkMaliciousConfig),
+    /// Checks validator data and prints a summary of it
+    #[command(arg_required_else_help = true)]
+    ValidateData(ValidateDataConfig),
 }
 
 fn main() -> anyhow::Result<()> {
-    let args = Cli::parse();
+    let matches = Cli::command().get_matches();
+    let args = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
     match args.command {
-        Command::Run(config) => run(&config),
+        Command::Run(mut config) => {
+            let run_matches = matches
+                .subcommand_matches("run")
+                .expect("run subcommand should be matched");
+            apply_scenario(&mut config, run_matches)?;
+            run(&config)
+        }
         Command::Download(dl_config) => download(&dl_config),
         Command::SeatStats(ss_config) => print_seat_stats(&ss_config),
+        Command::Analyze(analyze_config) => analyze(&analyze_config),
+        Command::Sweep(sweep_config) => sweep(&sweep_config),
+        Command::Epochs(epochs_config) => epochs(&epochs_config),
+        Command::Adaptive(adaptive_config) => adaptive(&adaptive_config),
+        Command::Tolerance(tolerance_config) => tolerance(&tolerance_config),
+        Command::MarkMalicious(mm_config) => mark_malicious(&mm_config),
+        Command::ValidateData(vd_config) => validate_data(&vd_config),
     }
 }
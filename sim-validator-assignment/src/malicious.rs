@@ -0,0 +1,344 @@
+use clap::{Args, ValueEnum};
+use num_rational::Ratio;
+use num_traits::{One, Zero};
+use regex::Regex;
+use serde::{Serialize, Serializer};
+use std::fmt::Display;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::rng::derive_seed;
+use crate::run::seed_or_random;
+use crate::validator::{read_validator_data, RawValidatorData};
+
+/// The domain of the seed choosing malicious validators, see [`derive_seed`].
+const SEED_DOMAIN: &[u8; 8] = b"maliciou";
+
+#[derive(Args, Debug)]
+pub struct MarkMaliciousConfig {
+    /// The file from which validator data is read. It is expected to contain a vector of
+    /// `RawValidatorData` serialized as JSON.
+    #[arg(long)]
+    pub validator_data: PathBuf,
+    /// The rule choosing malicious validators, see [`MaliciousRule`].
+    #[arg(long)]
+    pub rule: MaliciousRule,
+    /// The seed of the random number generator used by the `random-stake` rule.
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// The path of the file to which the updated validator data will be written.
+    #[arg(long)]
+    pub out: PathBuf,
+}
+
+/// A rule choosing which validators are malicious. All validators not chosen by the rule are
+/// honest, regardless of their previous `is_malicious` value.
+///
+/// Rules are parsed from strings of the form `<kind>:<argument>`.
+#[derive(Clone, Debug)]
+pub enum MaliciousRule {
+    /// `largest:<n>` chooses the `n` validators with the largest stake.
+    Largest(usize),
+    /// `smallest:<n>` chooses the `n` validators with the smallest stake.
+    Smallest(usize),
+    /// `random-stake:<ratio>` chooses validators in random order until their share of the total
+    /// stake reaches `ratio`, e.g. `random-stake:1/3`.
+    RandomStake(Ratio<u128>),
+    /// `accounts:<id>,<id>,...` chooses the validators with the given account ids.
+    Accounts(Vec<String>),
+    /// `regex:<pattern>` chooses the validators whose account id matches `pattern`.
+    Regex(Regex),
+}
+
+impl MaliciousRule {
+    /// Sets `is_malicious` of the validators in `raw_validator_data` according to the rule. The
+    /// rng seeded with `seed` is used only by [`MaliciousRule::RandomStake`].
+    pub fn apply(
+        &self,
+        raw_validator_data: &mut [RawValidatorData],
+        seed: u64,
+    ) -> anyhow::Result<()> {
+        let mut is_malicious = vec![false; raw_validator_data.len()];
+        match self {
+            Self::Largest(n) | Self::Smallest(n) => {
+                if *n > raw_validator_data.len() {
+                    anyhow::bail!(
+                        "cannot choose {n} of {} validators as malicious",
+                        raw_validator_data.len()
+                    );
+                }
+                // The sort is stable, so validators with equal stake are chosen in input order.
+                let mut indices = (0..raw_validator_data.len()).collect::<Vec<_>>();
+                match self {
+                    Self::Largest(_) => indices.sort_by(|&a, &b| {
+                        raw_validator_data[b]
+                            .stake
+                            .cmp(&raw_validator_data[a].stake)
+                    }),
+                    _ => indices.sort_by_key(|&idx| raw_validator_data[idx].stake),
+                }
+                for &idx in indices[..*n].iter() {
+                    is_malicious[idx] = true;
+                }
+            }
+            Self::RandomStake(fraction) => {
+                return mark_stake_fraction(
+                    raw_validator_data,
+                    StakeStrategy::Random,
+                    *fraction,
+                    seed,
+                );
+            }
+            Self::Accounts(account_ids) => {
+                for account_id in account_ids.iter() {
+                    let idx = raw_validator_data
+                        .iter()
+                        .position(|v| &v.account_id == account_id)
+                        .ok_or_else(|| {
+                            anyhow::anyhow!("no validator has account id {account_id}")
+                        })?;
+                    is_malicious[idx] = true;
+                }
+            }
+            Self::Regex(regex) => {
+                for (idx, v) in raw_validator_data.iter().enumerate() {
+                    is_malicious[idx] = regex.is_match(&v.account_id);
+                }
+            }
+        }
+
+        for (v, is_malicious) in raw_validator_data.iter_mut().zip(is_malicious) {
+            v.is_malicious = is_malicious;
+        }
+        Ok(())
+    }
+}
+
+/// The order in which validators are chosen as malicious until they hold a fraction of stake.
+#[derive(ValueEnum, Serialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StakeStrategy {
+    /// Choose validators with larger stake first.
+    Largest,
+    /// Choose validators with smaller stake first.
+    Smallest,
+    /// Choose validators in random order.
+    Random,
+}
+
+/// Marks validators in the order given by `strategy` as malicious until they hold at least
+/// `fraction` of the total stake. All other validators are marked honest. The rng seeded with
+/// `seed` is used only by [`StakeStrategy::Random`]. Its seed is derived from `seed`, so it is
+/// independent of the shuffles of seats in a simulation with the same seed.
+pub fn mark_stake_fraction(
+    raw_validator_data: &mut [RawValidatorData],
+    strategy: StakeStrategy,
+    fraction: Ratio<u128>,
+    seed: u64,
+) -> anyhow::Result<()> {
+    let stake = raw_validator_data
+        .iter()
+        .try_fold(0u128, |sum, v| sum.checked_add(v.stake))
+        .ok_or_else(|| anyhow::anyhow!("the sum of validator stakes overflows u128"))?;
+    if stake == 0 {
+        anyhow::bail!("validators hold no stake");
+    }
+
+    let mut indices = (0..raw_validator_data.len()).collect::<Vec<_>>();
+    match strategy {
+        StakeStrategy::Largest => indices.sort_by(|&a, &b| {
+            raw_validator_data[b]
+                .stake
+                .cmp(&raw_validator_data[a].stake)
+        }),
+        StakeStrategy::Smallest => indices.sort_by_key(|&idx| raw_validator_data[idx].stake),
+        StakeStrategy::Random => {
+            fastrand::Rng::with_seed(derive_seed(seed, SEED_DOMAIN)).shuffle(&mut indices)
+        }
+    }
+
+    for v in raw_validator_data.iter_mut() {
+        v.is_malicious = false;
+    }
+    let mut malicious_stake = 0;
+    for idx in indices {
+        if Ratio::new(malicious_stake, stake) >= fraction {
+            break;
+        }
+        malicious_stake += raw_validator_data[idx].stake;
+        raw_validator_data[idx].is_malicious = true;
+    }
+    Ok(())
+}
+
+impl FromStr for MaliciousRule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, argument) = s
+            .split_once(':')
+            .ok_or_else(|| anyhow::anyhow!("expected a rule of the form `<kind>:<argument>`"))?;
+        match kind {
+            "largest" => Ok(Self::Largest(argument.parse()?)),
+            "smallest" => Ok(Self::Smallest(argument.parse()?)),
+            "random-stake" => {
+                let fraction = argument
+                    .parse::<Ratio<u128>>()
+                    .map_err(|err| anyhow::anyhow!("invalid ratio `{argument}`: {err}"))?;
+                if fraction > Ratio::one() || fraction.is_zero() {
+                    anyhow::bail!("the fraction of malicious stake must be in (0, 1]");
+                }
+                Ok(Self::RandomStake(fraction))
+            }
+            "accounts" => Ok(Self::Accounts(
+                argument.split(',').map(|id| id.trim().to_owned()).collect(),
+            )),
+            "regex" => Ok(Self::Regex(Regex::new(argument)?)),
+            _ => anyhow::bail!(
+                "unknown rule `{kind}`, expected one of largest, smallest, random-stake, accounts, regex"
+            ),
+        }
+    }
+}
+
+impl Display for MaliciousRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Largest(n) => write!(f, "largest:{n}"),
+            Self::Smallest(n) => write!(f, "smallest:{n}"),
+            Self::RandomStake(fraction) => write!(f, "random-stake:{fraction}"),
+            Self::Accounts(account_ids) => write!(f, "accounts:{}", account_ids.join(",")),
+            Self::Regex(regex) => write!(f, "regex:{regex}"),
+        }
+    }
+}
+
+impl Serialize for MaliciousRule {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Marks validators as malicious according to the rule in `config` and writes the updated
+/// validator data as pretty printed JSON.
+pub fn mark_malicious(config: &MarkMaliciousConfig) -> anyhow::Result<()> {
+    let mut raw_validator_data = read_validator_data(config.validator_data.as_path())?;
+    let seed = seed_or_random(config.seed);
+    config.rule.apply(&mut raw_validator_data, seed)?;
+
+    let malicious = raw_validator_data.iter().filter(|v| v.is_malicious);
+    let malicious_stake: u128 = malicious.clone().map(|v| v.stake).sum();
+    let stake: u128 = raw_validator_data.iter().map(|v| v.stake).sum();
+    println!("num_malicious_validators\t{}", malicious.count());
+    println!("malicious_stake\t{malicious_stake}");
+    println!("stake\t{stake}");
+    if matches!(config.rule, MaliciousRule::RandomStake(_)) {
+        println!("seed\t{seed}");
+    }
+
+    let pretty_json = serde_json::to_string_pretty(&raw_validator_data)?;
+    let mut file = File::create(config.out.as_path())?;
+    file.write_all(pretty_json.as_bytes())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use num_rational::Ratio;
+
+    use super::{mark_stake_fraction, MaliciousRule, StakeStrategy};
+    use crate::validator::tests::new_test_raw_validator_data;
+
+    fn malicious_account_ids(rule: &str, seed: u64) -> Vec<String> {
+        let mut raw_validator_data = new_test_raw_validator_data();
+        let rule = rule.parse::<MaliciousRule>().unwrap();
+        rule.apply(&mut raw_validator_data, seed).unwrap();
+        raw_validator_data
+            .into_iter()
+            .filter(|v| v.is_malicious)
+            .map(|v| v.account_id)
+            .collect()
+    }
+
+    #[test]
+    fn test_apply_rules() {
+        assert_eq!(
+            malicious_account_ids("largest:2", 0),
+            vec!["validator_0", "validator_1"]
+        );
+        assert_eq!(
+            malicious_account_ids("smallest:2", 0),
+            vec!["validator_2", "validator_3"]
+        );
+        assert_eq!(
+            malicious_account_ids("accounts:validator_4, validator_10", 0),
+            vec!["validator_4", "validator_10"]
+        );
+        assert_eq!(
+            malicious_account_ids("regex:^validator_1[01]?$", 0),
+            vec!["validator_1", "validator_10", "validator_11"]
+        );
+
+        let mut raw_validator_data = new_test_raw_validator_data();
+        let stake: u128 = raw_validator_data.iter().map(|v| v.stake).sum();
+        let fraction = Ratio::new(1, 3);
+        MaliciousRule::RandomStake(fraction)
+            .apply(&mut raw_validator_data, 42)
+            .unwrap();
+        let malicious_stakes: Vec<u128> = raw_validator_data
+            .iter()
+            .filter(|v| v.is_malicious)
+            .map(|v| v.stake)
+            .collect();
+        let malicious_stake: u128 = malicious_stakes.iter().sum();
+        assert!(Ratio::new(malicious_stake, stake) >= fraction);
+        // Rules are applied deterministically for a given seed.
+        assert_eq!(
+            malicious_account_ids("random-stake:1/3", 42),
+            malicious_account_ids("random-stake:1/3", 42)
+        );
+    }
+
+    #[test]
+    fn test_mark_stake_fraction() {
+        let malicious_account_ids = |strategy| {
+            let mut raw_validator_data = new_test_raw_validator_data();
+            mark_stake_fraction(&mut raw_validator_data, strategy, Ratio::new(1, 2), 0).unwrap();
+            raw_validator_data
+                .into_iter()
+                .filter(|v| v.is_malicious)
+                .map(|v| v.account_id)
+                .collect::<Vec<_>>()
+        };
+        // The total stake is 1_800.
+        assert_eq!(
+            malicious_account_ids(StakeStrategy::Largest),
+            vec!["validator_0", "validator_1", "validator_3"]
+        );
+        assert_eq!(malicious_account_ids(StakeStrategy::Smallest).len(), 10);
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        for rule in [
+            "largest:3",
+            "smallest:1",
+            "random-stake:1/3",
+            "accounts:a,b",
+            "regex:^a.*",
+        ] {
+            assert_eq!(rule.parse::<MaliciousRule>().unwrap().to_string(), rule);
+        }
+        for rule in [
+            "largest",
+            "largest:-1",
+            "random-stake:4/3",
+            "foo:1",
+            "regex:(",
+        ] {
+            assert!(rule.parse::<MaliciousRule>().is_err(), "{rule}");
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+/// The format of files written by simulations.
+#[derive(ValueEnum, Serialize, Clone, Copy, PartialEq, Debug)]
+pub enum OutputFormat {
+    Csv,
+    Json,
+}
+
+/// Writes `record` to `path` in the given `format`.
+///
+/// A CSV file consists of a header and a single row. Nested fields are flattened into columns with
+/// dotted names, e.g. `config.num_shards` or `thresholds.0.num_corrupted_shards`.
+pub fn write_record(
+    record: &impl Serialize,
+    path: &Path,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let content = match format {
+        OutputFormat::Json => serde_json::to_string_pretty(record)?,
+        OutputFormat::Csv => to_csv(&[serde_json::to_value(record)?]),
+    };
+    write_file(path, &content)
+}
+
+/// Writes `records` to `path` in the given `format`, i.e. as a JSON array or as CSV with one row
+/// per record. The columns of CSV are taken from the first record.
+pub fn write_records<T: Serialize>(
+    records: &[T],
+    path: &Path,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let content = match format {
+        OutputFormat::Json => serde_json::to_string_pretty(records)?,
+        OutputFormat::Csv => to_csv(
+            &records
+                .iter()
+                .map(serde_json::to_value)
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+    };
+    write_file(path, &content)
+}
+
+/// A row of a table printed by [`print_table`].
+pub trait TableRow {
+    /// Returns the tab separated names of the columns.
+    fn header() -> String;
+    /// Returns the tab separated cells of the row.
+    fn cells(&self) -> String;
+}
+
+/// Prints `rows` as a tab separated table to stdout.
+pub fn print_table<R: TableRow>(rows: &[R]) {
+    println!("{}", R::header());
+    for row in rows {
+        println!("{}", row.cells());
+    }
+}
+
+fn write_file(path: &Path, content: &str) -> anyhow::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(content.as_bytes())?;
+    Ok(())
+}
+
+fn to_csv(values: &[Value]) -> String {
+    let mut csv = String::new();
+    for (idx, value) in values.iter().enumerate() {
+        let mut columns = vec![];
+        flatten(value, String::new(), &mut columns);
+        let (header, row): (Vec<_>, Vec<_>) = columns
+            .into_iter()
+            .map(|(name, value)| (escape_csv(&name), escape_csv(&value)))
+            .unzip();
+        if idx == 0 {
+            csv.push_str(&header.join(","));
+            csv.push('\n');
+        }
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Appends the leaves of `value` to `columns`, named by their dotted path below `prefix`.
+fn flatten(value: &Value, prefix: String, columns: &mut Vec<(String, String)>) {
+    let child_prefix = |key: &str| {
+        if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{prefix}.{key}")
+        }
+    };
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map {
+                flatten(child, child_prefix(key), columns);
+            }
+        }
+        Value::Array(items) => {
+            for (idx, child) in items.iter().enumerate() {
+                flatten(child, child_prefix(&idx.to_string()), columns);
+            }
+        }
+        Value::Null => columns.push((prefix, String::new())),
+        Value::String(s) => columns.push((prefix, s.clone())),
+        Value::Bool(_) | Value::Number(_) => columns.push((prefix, value.to_string())),
+    }
+}
+
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_csv;
+    use serde_json::json;
+
+    #[test]
+    fn test_to_csv() {
+        let value = json!({
+            "seed": 42,
+            "config": {"num_shards": 4, "validator_data": null, "name": "a,\"b\""},
+            "thresholds": [{"ratio": [1, 3]}, {"ratio": [2, 3]}],
+        });
+        insta::assert_snapshot!(to_csv(&[value.clone(), value]));
+    }
+}
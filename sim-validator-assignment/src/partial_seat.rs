@@ -1,5 +1,6 @@
 use serde::Serialize;
 
+use crate::rng::Shuffle;
 use crate::validator::Validator;
 
 /// Represents a partial seat filled by a particular validator.
@@ -26,24 +27,30 @@ impl<'validator> PartialSeat<'validator> {
         self.validator.get_is_malicious()
     }
 
+    pub fn get_account_id(&self) -> &'validator str {
+        self.validator.get_account_id()
+    }
+
     pub fn get_weight(&self) -> u128 {
         self.weight
     }
 }
 
-pub struct ShuffledPartialSeats<'seats> {
-    partial_seats: &'seats [PartialSeat<'seats>],
+pub struct ShuffledPartialSeats<'seats, 'validator> {
+    partial_seats: &'seats [PartialSeat<'validator>],
 }
 
-impl<'seats> ShuffledPartialSeats<'seats> {
-    /// Shuffles the input `partial_seats`.
-    // TODO(rand) do all shuffling operations in one generic fn
-    pub fn new(partial_seats: &'seats mut [PartialSeat<'seats>]) -> Self {
-        fastrand::shuffle(partial_seats);
+impl<'seats, 'validator> ShuffledPartialSeats<'seats, 'validator> {
+    /// Shuffles the input `partial_seats` in place using `rng`.
+    pub fn new(
+        partial_seats: &'seats mut [PartialSeat<'validator>],
+        rng: &mut impl Shuffle,
+    ) -> Self {
+        rng.shuffle(partial_seats);
         Self { partial_seats }
     }
 
-    pub fn get_partial_seats(&self) -> &[PartialSeat] {
+    pub fn get_partial_seats(&self) -> &'seats [PartialSeat<'validator>] {
         self.partial_seats
     }
 }
@@ -0,0 +1,371 @@
+use clap::ValueEnum;
+use rand_chacha::ChaCha20Rng;
+use rand_core::{RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::fs::read_to_string;
+use std::path::Path;
+
+/// The backends that may be used as source of randomness when shuffling seats.
+#[derive(ValueEnum, Serialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RngBackend {
+    /// The fast, non-cryptographic generator of [`fastrand`].
+    Fastrand,
+    /// A cryptographically secure generator based on the ChaCha20 stream cipher.
+    Chacha,
+    /// Replays a sequence of random numbers read from a file.
+    Replay,
+}
+
+/// A source of randomness that can shuffle slices.
+///
+/// Implementors only need to provide a stream of random `u64`. Shuffling is implemented once for
+/// all backends, hence two backends producing the same stream of `u64` produce the same shuffles.
+pub trait Shuffle {
+    /// Returns the next random `u64` of the stream.
+    fn next_u64(&mut self) -> u64;
+
+    /// Returns an index that is uniformly distributed in `0..bound`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bound` is 0.
+    fn next_index(&mut self, bound: usize) -> usize {
+        assert!(bound > 0, "bound should be greater than zero");
+        // Lemire's nearly divisionless method, which rejects values that would introduce a bias.
+        let bound = u64::try_from(bound).expect("bound should fit into u64");
+        let threshold = bound.wrapping_neg() % bound;
+        loop {
+            let product = u128::from(self.next_u64()) * u128::from(bound);
+            // Truncation is intended, the low bits decide about rejection.
+            if (product as u64) >= threshold {
+                return usize::try_from(product >> 64).expect("index is less than bound");
+            }
+        }
+    }
+
+    /// Shuffles `items` in place with the Fisher-Yates algorithm.
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.next_index(i + 1);
+            items.swap(i, j);
+        }
+    }
+}
+
+impl Shuffle for fastrand::Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.u64(..)
+    }
+}
+
+impl Shuffle for ChaCha20Rng {
+    fn next_u64(&mut self) -> u64 {
+        RngCore::next_u64(self)
+    }
+}
+
+/// Replays a fixed sequence of random numbers, starting over once the sequence is exhausted.
+///
+/// This allows feeding randomness recorded elsewhere (e.g. by another implementation of validator
+/// assignment) into a simulation.
+#[derive(Clone, Debug)]
+pub struct ReplayRng {
+    values: Vec<u64>,
+    position: usize,
+}
+
+impl ReplayRng {
+    /// Constructs a source replaying `values`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `values` is empty.
+    pub fn new(values: Vec<u64>) -> Self {
+        assert!(!values.is_empty(), "values to replay should not be empty");
+        Self {
+            values,
+            position: 0,
+        }
+    }
+
+    /// Reads values to replay from a file expected to contain `Vec<u64>` serialized as JSON.
+    pub fn from_file(file_path: &Path) -> anyhow::Result<Self> {
+        let file_content = read_to_string(file_path)?;
+        let values = serde_json::from_str::<Vec<u64>>(&file_content)?;
+        if values.is_empty() {
+            anyhow::bail!("replay file {} contains no values", file_path.display());
+        }
+        Ok(Self::new(values))
+    }
+}
+
+impl Shuffle for ReplayRng {
+    fn next_u64(&mut self) -> u64 {
+        let value = self.values[self.position];
+        self.position = (self.position + 1) % self.values.len();
+        value
+    }
+
+    /// Maps the next value to `0..bound` via modulo.
+    ///
+    /// Rejection sampling might loop forever on a replayed sequence, hence a (negligible) bias is
+    /// accepted in exchange for consuming exactly one value per index.
+    fn next_index(&mut self, bound: usize) -> usize {
+        assert!(bound > 0, "bound should be greater than zero");
+        let bound = u64::try_from(bound).expect("bound should fit into u64");
+        usize::try_from(self.next_u64() % bound).expect("index is less than bound")
+    }
+}
+
+/// The source of randomness used by a simulation, dispatching to the selected [`RngBackend`].
+#[derive(Clone, Debug)]
+pub enum SimRng {
+    Fastrand(fastrand::Rng),
+    Chacha(Box<ChaCha20Rng>),
+    Replay(ReplayRng),
+}
+
+impl SimRng {
+    /// Constructs the source of randomness for `backend` which yields the `stream`th of the
+    /// independent streams derived from `seed`.
+    ///
+    /// The replay backend ignores `seed` and reads values from `replay_file`, which is required
+    /// for that backend. It provides only a single stream.
+    pub fn new(
+        backend: RngBackend,
+        seed: u64,
+        stream: u64,
+        replay_file: Option<&Path>,
+    ) -> anyhow::Result<Self> {
+        let rng = match backend {
+            RngBackend::Fastrand => {
+                let seed = if stream == 0 {
+                    seed
+                } else {
+                    splitmix64(seed ^ splitmix64(stream))
+                };
+                Self::Fastrand(fastrand::Rng::with_seed(seed))
+            }
+            RngBackend::Chacha => {
+                let mut rng = ChaCha20Rng::seed_from_u64(seed);
+                rng.set_stream(stream);
+                Self::Chacha(Box::new(rng))
+            }
+            RngBackend::Replay => {
+                if stream != 0 {
+                    anyhow::bail!("the replay rng provides only a single stream");
+                }
+                match replay_file {
+                    Some(file_path) => Self::Replay(ReplayRng::from_file(file_path)?),
+                    None => anyhow::bail!("the replay rng requires a replay file"),
+                }
+            }
+        };
+        Ok(rng)
+    }
+}
+
+/// The state of a [`SimRng`], from which it can continue its sequence of random values.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum RngState {
+    Fastrand {
+        seed: u64,
+    },
+    Chacha {
+        seed: [u8; 32],
+        stream: u64,
+        word_pos: u128,
+    },
+    Replay {
+        position: usize,
+    },
+}
+
+impl SimRng {
+    pub fn state(&self) -> RngState {
+        match self {
+            Self::Fastrand(rng) => RngState::Fastrand {
+                seed: rng.get_seed(),
+            },
+            Self::Chacha(rng) => RngState::Chacha {
+                seed: rng.get_seed(),
+                stream: rng.get_stream(),
+                word_pos: rng.get_word_pos(),
+            },
+            Self::Replay(rng) => RngState::Replay {
+                position: rng.position,
+            },
+        }
+    }
+
+    /// Restores `state`, which must have been obtained from a rng with the same backend. A replay
+    /// rng must have been constructed from the same file.
+    pub fn restore(&mut self, state: &RngState) -> anyhow::Result<()> {
+        match (self, state) {
+            (Self::Fastrand(rng), RngState::Fastrand { seed }) => rng.seed(*seed),
+            (
+                Self::Chacha(rng),
+                RngState::Chacha {
+                    seed,
+                    stream,
+                    word_pos,
+                },
+            ) => {
+                let mut restored = ChaCha20Rng::from_seed(*seed);
+                restored.set_stream(*stream);
+                restored.set_word_pos(*word_pos);
+                **rng = restored;
+            }
+            (Self::Replay(rng), RngState::Replay { position }) => {
+                if *position >= rng.values.len() {
+                    anyhow::bail!(
+                        "replay position {position} exceeds the {} values to replay",
+                        rng.values.len()
+                    );
+                }
+                rng.position = *position;
+            }
+            (_, state) => anyhow::bail!("rng state {state:?} does not match the rng backend"),
+        }
+        Ok(())
+    }
+}
+
+/// Derives a seed from `seed` for randomness other than shuffling seats, e.g. choosing malicious
+/// validators. Distinct values of `domain` yield independent seeds, which are also independent of
+/// the streams of [`SimRng`] derived from `seed`.
+pub(crate) fn derive_seed(seed: u64, domain: &[u8; 8]) -> u64 {
+    splitmix64(seed ^ splitmix64(u64::from_be_bytes(*domain)))
+}
+
+/// The finalizer of the SplitMix64 generator, which maps similar inputs (like consecutive stream
+/// indices) to unrelated outputs.
+pub(crate) fn splitmix64(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+impl Shuffle for SimRng {
+    fn next_u64(&mut self) -> u64 {
+        match self {
+            Self::Fastrand(rng) => Shuffle::next_u64(rng),
+            Self::Chacha(rng) => Shuffle::next_u64(rng.as_mut()),
+            Self::Replay(rng) => rng.next_u64(),
+        }
+    }
+
+    fn next_index(&mut self, bound: usize) -> usize {
+        match self {
+            Self::Fastrand(rng) => rng.next_index(bound),
+            Self::Chacha(rng) => rng.next_index(bound),
+            Self::Replay(rng) => rng.next_index(bound),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{derive_seed, ReplayRng, RngBackend, Shuffle, SimRng};
+
+    #[test]
+    fn test_shuffle_is_permutation() {
+        for backend in [RngBackend::Fastrand, RngBackend::Chacha] {
+            let mut rng = SimRng::new(backend, 42, 0, None).unwrap();
+            let mut items: Vec<u32> = (0..100).collect();
+            rng.shuffle(&mut items);
+            items.sort();
+            assert_eq!(items, (0..100).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_shuffle_is_deterministic() {
+        for backend in [RngBackend::Fastrand, RngBackend::Chacha] {
+            let mut items_a: Vec<u32> = (0..100).collect();
+            let mut items_b = items_a.clone();
+            SimRng::new(backend, 42, 0, None)
+                .unwrap()
+                .shuffle(&mut items_a);
+            SimRng::new(backend, 42, 0, None)
+                .unwrap()
+                .shuffle(&mut items_b);
+            assert_eq!(items_a, items_b);
+        }
+    }
+
+    #[test]
+    fn test_next_index_in_bounds() {
+        let mut rng = SimRng::new(RngBackend::Chacha, 7, 0, None).unwrap();
+        for bound in 1..50 {
+            assert!(rng.next_index(bound) < bound);
+        }
+    }
+
+    #[test]
+    fn test_replay_rng() {
+        let mut rng = ReplayRng::new(vec![0, 1, 5]);
+        assert_eq!(rng.next_index(4), 0);
+        assert_eq!(rng.next_index(4), 1);
+        assert_eq!(rng.next_index(4), 1);
+        // The sequence starts over once it is exhausted.
+        assert_eq!(rng.next_index(10), 0);
+
+        let mut items = vec!['a', 'b', 'c'];
+        ReplayRng::new(vec![0]).shuffle(&mut items);
+        assert_eq!(items, vec!['b', 'c', 'a']);
+    }
+
+    #[test]
+    fn test_replay_requires_file() {
+        assert!(SimRng::new(RngBackend::Replay, 0, 0, None).is_err());
+    }
+
+    #[test]
+    fn test_restore_state() {
+        for backend in [RngBackend::Fastrand, RngBackend::Chacha] {
+            let mut rng = SimRng::new(backend, 42, 3, None).unwrap();
+            rng.next_u64();
+            let state = rng.state();
+            let expected: Vec<u64> = (0..4).map(|_| rng.next_u64()).collect();
+
+            let mut restored = SimRng::new(backend, 0, 0, None).unwrap();
+            restored.restore(&state).unwrap();
+            let values: Vec<u64> = (0..4).map(|_| restored.next_u64()).collect();
+            assert_eq!(values, expected);
+        }
+
+        let mut rng = SimRng::new(RngBackend::Fastrand, 0, 0, None).unwrap();
+        let state = SimRng::new(RngBackend::Chacha, 0, 0, None).unwrap().state();
+        assert!(rng.restore(&state).is_err());
+    }
+
+    #[test]
+    fn test_streams_differ() {
+        for backend in [RngBackend::Fastrand, RngBackend::Chacha] {
+            let mut stream_0 = SimRng::new(backend, 42, 0, None).unwrap();
+            let mut stream_1 = SimRng::new(backend, 42, 1, None).unwrap();
+            let values_0: Vec<u64> = (0..4).map(|_| stream_0.next_u64()).collect();
+            let values_1: Vec<u64> = (0..4).map(|_| stream_1.next_u64()).collect();
+            assert_ne!(values_0, values_1);
+        }
+        assert!(SimRng::new(RngBackend::Replay, 42, 1, None).is_err());
+    }
+
+    #[test]
+    fn test_derive_seed() {
+        let seed = 42;
+        let mut stream_0 = SimRng::new(RngBackend::Fastrand, seed, 0, None).unwrap();
+        let mut derived = fastrand::Rng::with_seed(derive_seed(seed, b"domain_a"));
+        assert_ne!(stream_0.next_u64(), derived.u64(..));
+        assert_ne!(
+            derive_seed(seed, b"domain_a"),
+            derive_seed(seed, b"domain_b")
+        );
+        assert_eq!(
+            derive_seed(seed, b"domain_a"),
+            derive_seed(seed, b"domain_a")
+        );
+    }
+}
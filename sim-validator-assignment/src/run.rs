@@ -1,23 +1,75 @@
+use crate::adversary::replace_malicious_by_adversary;
+use crate::checkpoint::{config_hash, validator_data_hash, Checkpoint};
+use crate::compact::{CompactPopulation, ValidatorSet};
 use crate::config::Config;
-use crate::partial_seat::ShuffledPartialSeats;
-use crate::seat::ShuffledSeats;
+use crate::counters::Counters;
+use crate::importance_sampling::run_importance_sampling;
+use crate::output::write_record;
+use crate::rng::{Shuffle, SimRng};
+use crate::run_result::{RunResult, ThresholdResult};
+use crate::seat_price::StakePerSeat;
 use crate::shard::Shard;
+use crate::validation::validate_config;
 use crate::validator::{
-    new_ordered_partial_seats, new_ordered_seats, parse_raw_validator_data, read_validator_data,
-    RawValidatorData,
+    parse_raw_validator_data, read_validator_data, PopulationStats, RawValidatorData,
 };
 use num_rational::Ratio;
 use num_traits::ToPrimitive;
+use std::time::Instant;
 
 pub fn run(config: &Config) -> anyhow::Result<()> {
-    let raw_validator_data = match &config.validator_data {
+    let start = Instant::now();
+    let mut raw_validator_data = match &config.validator_data {
         Some(file_path) => read_validator_data(file_path.as_path())?,
         None => mock_validator_data(),
     };
-
-    let (population_stats, validators) =
-        parse_raw_validator_data(&raw_validator_data, config.stake_per_seat);
-
+    // The seed is determined before choosing malicious validators, which may require randomness.
+    let checkpoint = match &config.checkpoint {
+        Some(file_path) if config.resume => Some(Checkpoint::read(file_path)?),
+        _ => None,
+    };
+    let seed = match &checkpoint {
+        Some(checkpoint) => checkpoint.seed,
+        None => seed_or_random(config.seed),
+    };
+    if let Some(rule) = &config.mark_malicious {
+        rule.apply(&mut raw_validator_data, seed)?;
+    }
+    // The adversary requires a valid config, which is validated again for its validators below.
+    validate_config(config, &raw_validator_data)?;
+    if let Some(budget) = config.adversary_budget {
+        let (split, validators) = replace_malicious_by_adversary(
+            &raw_validator_data,
+            budget,
+            config.stake_per_seat.get(),
+            config.include_partial_seats,
+        )?;
+        println!(
+            "The adversary splits its budget into {} validators holding one seat each, {} stake {}.",
+            split.num_full_seat_accounts,
+            split.remainder,
+            if config.include_partial_seats {
+                "remains for a partial seat"
+            } else {
+                "remains unused"
+            }
+        );
+        raw_validator_data = validators;
+    }
+    let (resolved_config, population_stats, population) =
+        prepare_population(config, &raw_validator_data)?;
+    if config.stake_per_seat == StakePerSeat::Auto {
+        println!(
+            "stake_per_seat computed from validator data: {}",
+            resolved_config.stake_per_seat.get()
+        );
+    }
+    // The printed config shows the seed actually used, e.g. the one restored from a checkpoint.
+    let config = &Config {
+        seed: Some(seed),
+        ..resolved_config
+    };
+    println!("config: {}", serde_json::to_string(config)?);
     println!("population_stats: {:?}", population_stats);
     println!(
         "malicious_stake / stake ≈ {:.5}",
@@ -32,58 +84,272 @@ pub fn run(config: &Config) -> anyhow::Result<()> {
             .unwrap()
     );
 
-    if population_stats.seats < u64::from(config.num_shards) * config.seats_per_shard {
+    if let Some(checkpoint) = &checkpoint {
+        checkpoint.check_resumable(config, &raw_validator_data)?;
+        println!(
+            "Resuming from checkpoint after {} blocks.",
+            checkpoint.counters.num_blocks
+        );
+    }
+    let mut rngs = (0..config.threads)
+        .map(|stream| {
+            SimRng::new(
+                config.rng,
+                seed,
+                stream.into(),
+                config.replay_file.as_deref(),
+            )
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    if config.importance_sampling {
+        let (num_samples, thresholds) =
+            run_importance_sampling(config, &population_stats, &population, &mut rngs)?;
+        write_result(
+            config,
+            &RunResult {
+                config,
+                population_stats: &population_stats,
+                seed,
+                num_blocks: num_samples,
+                wall_clock_seconds: start.elapsed().as_secs_f64(),
+                thresholds,
+                liveness_failure: None,
+                safety_failure: None,
+                counters: None,
+            },
+        )?;
+        log_seed(seed, config.threads);
+        return Ok(());
+    }
+
+    let mut counters = match checkpoint {
+        Some(checkpoint) => {
+            if checkpoint.rng_states.len() != rngs.len() {
+                anyhow::bail!("the checkpoint does not contain the state of every rng");
+            }
+            for (rng, state) in rngs.iter_mut().zip(checkpoint.rng_states.iter()) {
+                rng.restore(state)?;
+            }
+            checkpoint.counters
+        }
+        None => Counters::new(
+            config.num_shards,
+            &config.max_malicious_stake_per_shard,
+            config.histogram_buckets,
+        ),
+    };
+    let checkpoint_hashes = match &config.checkpoint {
+        Some(_) => Some((
+            config_hash(config)?,
+            validator_data_hash(&raw_validator_data)?,
+        )),
+        None => None,
+    };
+    let block_limit = config.block_limit();
+
+    while counters.num_blocks < block_limit {
+        let num_batch_blocks = HEARTBEAT_INTERVAL.min(block_limit - counters.num_blocks);
+        for worker_counters in simulate_batch(&mut rngs, num_batch_blocks, |rng, num_blocks| {
+            simulate_blocks(config, &population, rng, num_blocks)
+        })? {
+            counters.merge(&worker_counters);
+        }
+
+        log_heartbeat(&counters);
+        if let (Some(file_path), Some((config_hash, validator_data_hash))) =
+            (&config.checkpoint, checkpoint_hashes)
+        {
+            Checkpoint {
+                config_hash,
+                validator_data_hash,
+                seed,
+                counters: counters.clone(),
+                rng_states: rngs.iter().map(SimRng::state).collect(),
+            }
+            .write(file_path)?;
+        }
+        if config.is_precise_enough(counters.max_relative_error()) {
+            println!(
+                "Reached the target relative error after {} blocks.",
+                counters.num_blocks
+            );
+            break;
+        }
+    }
+
+    print_summary(config, &counters);
+    if let (Some(histogram), Some(path)) = (
+        counters.malicious_ratio_histogram.as_ref(),
+        config.histogram_output.as_ref(),
+    ) {
+        histogram.write(path, config.histogram_format)?;
+        println!(
+            "Wrote the histogram of the malicious ratio to {}",
+            path.display()
+        );
+    }
+    let has_blocks = counters.num_blocks > 0;
+    write_result(
+        config,
+        &RunResult {
+            config,
+            population_stats: &population_stats,
+            seed,
+            num_blocks: counters.num_blocks,
+            wall_clock_seconds: start.elapsed().as_secs_f64(),
+            thresholds: ThresholdResult::from_counters(&counters),
+            liveness_failure: has_blocks.then(|| counters.liveness_failure()),
+            safety_failure: has_blocks.then(|| counters.safety_failure()),
+            counters: Some(&counters),
+        },
+    )?;
+    log_seed(seed, config.threads);
+    Ok(())
+}
+
+/// Returns `seed`, or a random seed if none is given.
+pub(crate) fn seed_or_random(seed: Option<u64>) -> u64 {
+    seed.unwrap_or_else(|| fastrand::u64(..))
+}
+
+/// Validates `config` for `raw_validator_data` and returns the config with a fixed
+/// `stake_per_seat`, resolved if it is `auto`, along with the stats and the compact representation
+/// of the validators. Fails if validators do not cover the seats required by `config`.
+pub(crate) fn prepare_population(
+    config: &Config,
+    raw_validator_data: &[RawValidatorData],
+) -> anyhow::Result<(Config, PopulationStats, CompactPopulation)> {
+    validate_config(config, raw_validator_data)?;
+    let stake_per_seat = config
+        .stake_per_seat
+        .resolve(raw_validator_data, config.total_seats())?;
+    let config = Config {
+        stake_per_seat: StakePerSeat::Fixed(stake_per_seat),
+        ..config.clone()
+    };
+    let (population_stats, validators) =
+        parse_raw_validator_data(raw_validator_data, stake_per_seat)?;
+    check_seat_coverage(&population_stats, config.total_seats())?;
+    let population = CompactPopulation::new(&validators, stake_per_seat)?;
+    Ok((config, population_stats, population))
+}
+
+/// Fails if the seats of validators described by `population_stats` are fewer than
+/// `required_seats`.
+pub(crate) fn check_seat_coverage(
+    population_stats: &PopulationStats,
+    required_seats: u64,
+) -> anyhow::Result<()> {
+    if population_stats.seats < required_seats {
         anyhow::bail!(
-            "Validators cover {} seats, config requires {} seats",
-            population_stats.seats,
-            config.total_seats()
+            "Validators cover {} seats, config requires {required_seats} seats",
+            population_stats.seats
         )
     }
+    Ok(())
+}
 
-    let mut num_corrupted_shards = 0;
+/// The number of blocks simulated between two heartbeats.
+const HEARTBEAT_INTERVAL: u64 = 100_000;
 
-    for block_height in 0..config.num_blocks {
-        let mut seats = new_ordered_seats(&validators);
-        let shuffled_seats = ShuffledSeats::new(&mut seats);
+/// Runs `simulate` for `num_blocks` blocks split evenly across one thread per element of `rngs`
+/// and returns the results of all threads.
+///
+/// The split depends only on `num_blocks` and the number of `rngs`, so results are deterministic
+/// for given states of `rngs`.
+pub(crate) fn simulate_batch<T: Send>(
+    rngs: &mut [SimRng],
+    num_blocks: u64,
+    simulate: impl Fn(&mut SimRng, u64) -> anyhow::Result<T> + Sync,
+) -> anyhow::Result<Vec<T>> {
+    let num_workers = u64::try_from(rngs.len()).expect("number of rngs should fit into u64");
+    let simulate = &simulate;
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = rngs
+            .iter_mut()
+            .zip(0..)
+            .map(|(rng, worker_idx)| {
+                let num_worker_blocks =
+                    num_blocks / num_workers + u64::from(worker_idx < num_blocks % num_workers);
+                scope.spawn(move || simulate(rng, num_worker_blocks))
+            })
+            .collect();
 
-        let mut partial_seats = if config.include_partial_seats {
-            new_ordered_partial_seats(&validators, config.stake_per_seat)
-        } else {
-            Vec::new()
-        };
-        let shuffled_partial_seats = ShuffledPartialSeats::new(&mut partial_seats);
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .map_err(|_| anyhow::anyhow!("a simulation thread panicked"))?
+            })
+            .collect()
+    })
+}
 
+/// Simulates `num_blocks` blocks using `rng` and returns the observed corruption events.
+///
+/// Seats are represented in compact form and their buffers are constructed once and reshuffled in
+/// place for every block, hence the loop over blocks requires no heap allocations.
+pub fn simulate_blocks(
+    config: &Config,
+    population: &CompactPopulation,
+    rng: &mut impl Shuffle,
+    num_blocks: u64,
+) -> anyhow::Result<Counters> {
+    let mut seats = population.new_ordered_seats();
+    let mut partial_seats = if config.include_partial_seats {
+        population.new_ordered_partial_seats()
+    } else {
+        Vec::new()
+    };
+    let mut distinct_validators = ValidatorSet::new(population);
+    let mut counters = Counters::new(
+        config.num_shards,
+        &config.max_malicious_stake_per_shard,
+        config.histogram_buckets,
+    );
+    let mut num_corrupted_shards = vec![0; config.max_malicious_stake_per_shard.len()];
+
+    for _ in 0..num_blocks {
+        rng.shuffle(&mut seats);
+        rng.shuffle(&mut partial_seats);
+
+        num_corrupted_shards.fill(0);
         for shard_idx in 0..config.num_shards {
             let shard_idx = usize::from(shard_idx);
-            let shard_seats =
-                config.collect_seats_for_shard(shard_idx, shuffled_seats.get_seats())?;
-            let shard_partial_seats = config.collect_partial_seats_for_shard(
-                shard_idx,
-                shuffled_partial_seats.get_partial_seats(),
+            let shard_seats = config.collect_seats_for_shard(shard_idx, &seats)?;
+            let shard_partial_seats =
+                config.collect_partial_seats_for_shard(shard_idx, &partial_seats)?;
+            let shard = Shard::from_compact(
+                config,
+                population,
+                shard_seats,
+                shard_partial_seats,
+                &mut distinct_validators,
             )?;
-            let shard = Shard::new(config, shard_seats, shard_partial_seats)?;
-            if shard.is_corrupted(config) {
-                num_corrupted_shards += 1;
+            for (num_corrupted, &threshold) in num_corrupted_shards
+                .iter_mut()
+                .zip(config.max_malicious_stake_per_shard.iter())
+            {
+                if shard.is_corrupted(threshold) {
+                    *num_corrupted += 1;
+                }
+            }
+            counters
+                .shard_statuses
+                .add(shard.status(config.liveness_threshold, config.safety_threshold));
+            if let Some(histogram) = counters.malicious_ratio_histogram.as_mut() {
+                histogram.add(shard.malicious_ratio());
             }
         }
-
-        if block_height % 100_000 == 0 {
-            log_heartbeat(
-                block_height,
-                block_height * u64::from(config.num_shards),
-                num_corrupted_shards,
-            );
-        }
+        counters.add_block(config.num_shards, &num_corrupted_shards);
     }
 
-    println!(
-        "Simulated {} blocks with {} shards each. The number of corrupted shards out of total shards is {} / {}",
-        config.num_blocks, config.num_shards, num_corrupted_shards, config.num_blocks * u64::from(config.num_shards)
-    );
-    Ok(())
+    Ok(counters)
 }
 
-fn mock_validator_data() -> Vec<RawValidatorData> {
+pub(crate) fn mock_validator_data() -> Vec<RawValidatorData> {
     // Mock a set of validators corresponding to the one used in Table 4 of this paper
     // https://www.montrealblockchainlab.com/New%20Mathematical%20Model.pdf
     // We model 1/3 of validators as malicious which corresponds to Class B (see Table 1).
@@ -91,6 +357,163 @@ fn mock_validator_data() -> Vec<RawValidatorData> {
     crate::mocks::new_validators(num_validators, 1, num_validators / 3)
 }
 
-fn log_heartbeat(block_height: u64, num_simulated_shards: u64, num_corrupted_shards: u64) {
-    println!("heartbeat(block_height: {block_height}): {num_corrupted_shards} / {num_simulated_shards} shards corrupted");
+fn log_heartbeat(counters: &Counters) {
+    let thresholds = counters
+        .thresholds
+        .iter()
+        .enumerate()
+        .map(|(threshold_idx, threshold)| {
+            let interval = counters.shard_corruption(threshold_idx).confidence_interval;
+            format!(
+                "{}: {} / {} shards corrupted, 95% confidence interval [{:.4e}, {:.4e}], {} blocks with corrupted shards",
+                threshold.max_malicious_stake_per_shard,
+                threshold.num_corrupted_shards,
+                counters.num_shards,
+                interval.lower,
+                interval.upper,
+                threshold.num_corrupted_blocks
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("; ");
+    println!(
+        "heartbeat(simulated_blocks: {}): {thresholds}",
+        counters.num_blocks
+    );
+}
+
+fn print_summary(config: &Config, counters: &Counters) {
+    println!(
+        "Simulated {} blocks with {} shards each, i.e. {} shards",
+        counters.num_blocks, config.num_shards, counters.num_shards
+    );
+    if counters.num_blocks == 0 {
+        return;
+    }
+
+    println!("max_malicious_stake_per_shard\tcorrupted_shards\tp(shard corrupted)\t95% confidence interval\trelative error\tcorrupted_blocks\tp(any shard in block corrupted)\t95% confidence interval");
+    for (threshold_idx, threshold) in counters.thresholds.iter().enumerate() {
+        let shard = counters.shard_corruption(threshold_idx);
+        let block = counters.block_corruption(threshold_idx);
+        println!(
+            "{}\t{}\t{:.4e}\t[{:.4e}, {:.4e}]\t{:.5}\t{}\t{:.4e}\t[{:.4e}, {:.4e}]",
+            threshold.max_malicious_stake_per_shard,
+            threshold.num_corrupted_shards,
+            shard.estimate,
+            shard.confidence_interval.lower,
+            shard.confidence_interval.upper,
+            shard.relative_error,
+            threshold.num_corrupted_blocks,
+            block.estimate,
+            block.confidence_interval.lower,
+            block.confidence_interval.upper,
+        );
+    }
+
+    println!();
+    println!(
+        "Shards by status: {} healthy, {} liveness compromised, {} safety compromised",
+        counters.shard_statuses.healthy,
+        counters.shard_statuses.liveness_compromised,
+        counters.shard_statuses.safety_compromised
+    );
+    for (label, threshold, estimate) in [
+        (
+            "p(shard liveness compromised)",
+            config.liveness_threshold,
+            counters.liveness_failure(),
+        ),
+        (
+            "p(shard safety compromised)",
+            config.safety_threshold,
+            counters.safety_failure(),
+        ),
+    ] {
+        println!(
+            "Estimated {label} = {:.4e} (malicious stake above {threshold}), 95% confidence interval [{:.4e}, {:.4e}], relative error {:.5}",
+            estimate.estimate,
+            estimate.confidence_interval.lower,
+            estimate.confidence_interval.upper,
+            estimate.relative_error
+        );
+    }
+
+    println!();
+    let header = counters
+        .thresholds
+        .iter()
+        .map(|threshold| threshold.max_malicious_stake_per_shard.to_string())
+        .collect::<Vec<_>>()
+        .join("\t");
+    println!("corrupted_shards_per_block\t{header}");
+    for num_corrupted_shards in 0..=usize::from(config.num_shards) {
+        let num_blocks = counters
+            .thresholds
+            .iter()
+            .map(|threshold| threshold.corrupted_shards_per_block[num_corrupted_shards].to_string())
+            .collect::<Vec<_>>()
+            .join("\t");
+        println!("{num_corrupted_shards}\t{num_blocks}");
+    }
+}
+
+/// Writes `result` to `config.output`, if it is set.
+fn write_result(config: &Config, result: &RunResult) -> anyhow::Result<()> {
+    if let Some(path) = &config.output {
+        write_record(result, path, config.format)?;
+        println!("Wrote the result to {}", path.display());
+    }
+    Ok(())
+}
+
+/// Prints how to reproduce a simulation which used `seed` and `threads`.
+pub(crate) fn log_seed(seed: u64, threads: u16) {
+    println!(
+        "The simulation used seed {seed} and {threads} thread(s). Pass `--seed {seed} --threads {threads}` to reproduce it."
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{simulate_batch, simulate_blocks};
+    use crate::compact::CompactPopulation;
+    use crate::config::Config;
+    use crate::counters::Counters;
+    use crate::rng::SimRng;
+    use crate::validator::parse_raw_validator_data;
+    use crate::validator::tests::new_test_raw_validator_data;
+
+    fn simulate_with_seed(config: &Config, seed: u64) -> Counters {
+        let (_, validators) =
+            parse_raw_validator_data(&new_test_raw_validator_data(), config.stake_per_seat.get())
+                .unwrap();
+        let population = CompactPopulation::new(&validators, config.stake_per_seat.get()).unwrap();
+        let mut rngs = (0..config.threads)
+            .map(|stream| SimRng::new(config.rng, seed, stream.into(), None).unwrap())
+            .collect::<Vec<_>>();
+        let mut counters = Counters::new(
+            config.num_shards,
+            &config.max_malicious_stake_per_shard,
+            config.histogram_buckets,
+        );
+        for worker_counters in simulate_batch(&mut rngs, config.block_limit(), |rng, num_blocks| {
+            simulate_blocks(config, &population, rng, num_blocks)
+        })
+        .unwrap()
+        {
+            counters.merge(&worker_counters);
+        }
+        counters
+    }
+
+    #[test]
+    fn test_simulation_is_reproducible_with_seed() {
+        let mut config = Config::new_mock(true);
+        config.threads = 2;
+        config.histogram_buckets = Some(10);
+
+        let counters = simulate_with_seed(&config, 42);
+        assert_eq!(counters.num_blocks, 1_000);
+        assert_eq!(counters, simulate_with_seed(&config, 42));
+    }
 }
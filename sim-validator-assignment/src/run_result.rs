@@ -0,0 +1,59 @@
+use num_rational::Ratio;
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::counters::{Counters, ProbabilityEstimate};
+use crate::validator::PopulationStats;
+
+/// The machine-readable result of [`run`](crate::run::run), written to `config.output`.
+#[derive(Serialize, Debug)]
+pub struct RunResult<'a> {
+    pub config: &'a Config,
+    pub population_stats: &'a PopulationStats,
+    pub seed: u64,
+    /// The number of simulated blocks, or samples drawn in case of importance sampling.
+    pub num_blocks: u64,
+    pub wall_clock_seconds: f64,
+    /// The estimates for each threshold in `config.max_malicious_stake_per_shard`.
+    pub thresholds: Vec<ThresholdResult>,
+    /// The estimated probability that a shard's liveness is compromised. It is not available for
+    /// importance sampling.
+    pub liveness_failure: Option<ProbabilityEstimate>,
+    /// The estimated probability that a shard's safety is compromised. It is not available for
+    /// importance sampling.
+    pub safety_failure: Option<ProbabilityEstimate>,
+    /// The raw counts of a simulation. They are not available for importance sampling.
+    pub counters: Option<&'a Counters>,
+}
+
+/// The estimates with respect to one threshold of malicious stake.
+#[derive(Serialize, Debug)]
+pub struct ThresholdResult {
+    pub max_malicious_stake_per_shard: Ratio<u128>,
+    /// The number of corrupted shards, or corrupted samples in case of importance sampling.
+    pub num_corrupted_shards: u64,
+    pub shard_corruption: ProbabilityEstimate,
+    /// The estimated probability that any shard in a block is corrupted. It is not available for
+    /// importance sampling.
+    pub block_corruption: Option<ProbabilityEstimate>,
+}
+
+impl ThresholdResult {
+    /// Returns the results of a simulation for every threshold counted by `counters`.
+    pub fn from_counters(counters: &Counters) -> Vec<Self> {
+        if counters.num_blocks == 0 {
+            return vec![];
+        }
+        counters
+            .thresholds
+            .iter()
+            .enumerate()
+            .map(|(threshold_idx, threshold)| Self {
+                max_malicious_stake_per_shard: threshold.max_malicious_stake_per_shard,
+                num_corrupted_shards: threshold.num_corrupted_shards,
+                shard_corruption: counters.shard_corruption(threshold_idx),
+                block_corruption: Some(counters.block_corruption(threshold_idx)),
+            })
+            .collect()
+    }
+}
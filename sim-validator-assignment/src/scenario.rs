@@ -0,0 +1,470 @@
+use clap::parser::ValueSource;
+use clap::{ArgMatches, ValueEnum};
+use num_rational::Ratio;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+use std::fmt::Display;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::config::Config;
+use crate::malicious::MaliciousRule;
+use crate::output::OutputFormat;
+use crate::rng::RngBackend;
+use crate::seat_price::StakePerSeat;
+use crate::shard::CorruptionMetric;
+
+/// Pairs of fields which cannot be used together. A field of the scenario is ignored if it
+/// conflicts with an argument passed on the command line.
+const CONFLICTS: [(&str, &str); 5] = [
+    ("num_blocks", "target_relative_error"),
+    ("num_blocks", "max_blocks"),
+    ("mark_malicious", "adversary_budget"),
+    ("histogram_buckets", "importance_sampling"),
+    ("checkpoint", "importance_sampling"),
+];
+
+/// Pairs of fields where the first requires the second, checked after the scenario is merged.
+/// `rng = replay` refers to the `replay` rng rather than any value of `rng`.
+const REQUIRES: [(&str, &str); 6] = [
+    ("target_relative_error", "max_blocks"),
+    ("max_blocks", "target_relative_error"),
+    ("histogram_buckets", "histogram_output"),
+    ("histogram_output", "histogram_buckets"),
+    ("resume", "checkpoint"),
+    ("rng = replay", "replay_file"),
+];
+
+/// The fields of a scenario file, named like those of [`Config`]. All fields are optional.
+///
+/// Values which are given as strings on the command line, e.g. ratios like `"1/3"` or `u128`
+/// values exceeding the range of TOML integers, are given as strings in the file as well.
+#[derive(Deserialize, Default, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub num_blocks: Option<u64>,
+    pub target_relative_error: Option<f64>,
+    pub max_blocks: Option<u64>,
+    pub num_shards: Option<u16>,
+    pub seats_per_shard: Option<u64>,
+    #[serde(default, deserialize_with = "parse")]
+    pub stake_per_seat: Option<StakePerSeat>,
+    #[serde(default, deserialize_with = "parse_list")]
+    pub max_malicious_stake_per_shard: Option<Vec<Ratio<u128>>>,
+    #[serde(default, deserialize_with = "parse")]
+    pub liveness_threshold: Option<Ratio<u128>>,
+    #[serde(default, deserialize_with = "parse")]
+    pub safety_threshold: Option<Ratio<u128>>,
+    #[serde(default, deserialize_with = "parse_value_enum")]
+    pub corruption_metric: Option<CorruptionMetric>,
+    pub validator_data: Option<PathBuf>,
+    #[serde(default, deserialize_with = "parse")]
+    pub mark_malicious: Option<MaliciousRule>,
+    #[serde(default, deserialize_with = "parse")]
+    pub adversary_budget: Option<u128>,
+    pub include_partial_seats: Option<bool>,
+    #[serde(default, deserialize_with = "parse")]
+    pub seed: Option<u64>,
+    #[serde(default, deserialize_with = "parse_value_enum")]
+    pub rng: Option<RngBackend>,
+    pub replay_file: Option<PathBuf>,
+    pub threads: Option<u16>,
+    pub importance_sampling: Option<bool>,
+    pub histogram_buckets: Option<u16>,
+    pub histogram_output: Option<PathBuf>,
+    #[serde(default, deserialize_with = "parse_value_enum")]
+    pub histogram_format: Option<OutputFormat>,
+    pub output: Option<PathBuf>,
+    #[serde(default, deserialize_with = "parse_value_enum")]
+    pub format: Option<OutputFormat>,
+    pub checkpoint: Option<PathBuf>,
+    pub resume: Option<bool>,
+}
+
+/// Reads the scenario file of `config`, if any, and merges it into `config`.
+///
+/// `matches` are the matches of the `run` subcommand from which `config` was parsed. A field of
+/// the scenario overrides the value of `config` unless the corresponding argument was passed on
+/// the command line.
+pub fn apply_scenario(config: &mut Config, matches: &ArgMatches) -> anyhow::Result<()> {
+    let Some(file_path) = config.config.clone() else {
+        return Ok(());
+    };
+    let scenario = Scenario::read(&file_path)?;
+    scenario.merge_into(config, matches)
+}
+
+impl Scenario {
+    /// Reads a scenario file, parsed as JSON if its extension is `json` and as TOML otherwise.
+    pub fn read(file_path: &Path) -> anyhow::Result<Self> {
+        let file_content = read_to_string(file_path)?;
+        let scenario = match file_path
+            .extension()
+            .and_then(|extension| extension.to_str())
+        {
+            Some("json") => serde_json::from_str(&file_content)?,
+            _ => toml::from_str(&file_content)?,
+        };
+        Ok(scenario)
+    }
+
+    /// Sets the fields of `config` which are set in the scenario, unless the corresponding
+    /// argument is passed on the command line according to `matches`.
+    pub fn merge_into(self, config: &mut Config, matches: &ArgMatches) -> anyhow::Result<()> {
+        let from_cli = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+        let set_fields = self.set_fields();
+        let is_set = |id: &str| set_fields.contains(&id);
+        for (a, b) in CONFLICTS {
+            if is_set(a) && is_set(b) && !from_cli(a) && !from_cli(b) {
+                anyhow::bail!("the scenario must not set both `{a}` and `{b}`");
+            }
+        }
+        let is_overridden = |id: &str| {
+            from_cli(id)
+                || CONFLICTS
+                    .iter()
+                    .any(|&(a, b)| (a == id && from_cli(b)) || (b == id && from_cli(a)))
+        };
+
+        macro_rules! merge {
+            ($($field:ident),* $(,)?) => {
+                $(
+                    if let Some(value) = self.$field {
+                        if !is_overridden(stringify!($field)) {
+                            config.$field = value;
+                        }
+                    }
+                )*
+            };
+        }
+        macro_rules! merge_option {
+            ($($field:ident),* $(,)?) => {
+                $(
+                    if let Some(value) = self.$field {
+                        if !is_overridden(stringify!($field)) {
+                            config.$field = Some(value);
+                        }
+                    }
+                )*
+            };
+        }
+        merge!(
+            num_shards,
+            seats_per_shard,
+            stake_per_seat,
+            max_malicious_stake_per_shard,
+            liveness_threshold,
+            safety_threshold,
+            corruption_metric,
+            include_partial_seats,
+            rng,
+            threads,
+            importance_sampling,
+            histogram_format,
+            format,
+            resume,
+        );
+        merge_option!(
+            num_blocks,
+            target_relative_error,
+            max_blocks,
+            validator_data,
+            mark_malicious,
+            adversary_budget,
+            seed,
+            replay_file,
+            histogram_buckets,
+            histogram_output,
+            output,
+            checkpoint,
+        );
+
+        // Required arguments have placeholder values if `config` is passed, hence they must be
+        // set by either the command line or the scenario.
+        for id in [
+            "num_shards",
+            "seats_per_shard",
+            "stake_per_seat",
+            "max_malicious_stake_per_shard",
+        ] {
+            if !from_cli(id) && !is_set(id) {
+                anyhow::bail!("`{id}` must be passed on the command line or set in the scenario");
+            }
+        }
+        for (a, b) in REQUIRES {
+            if is_present(config, a) && !is_present(config, b) {
+                anyhow::bail!(
+                    "`{a}` requires `{b}` to be passed on the command line or set in the scenario"
+                );
+            }
+        }
+        if config.num_blocks.is_none() && config.target_relative_error.is_none() {
+            anyhow::bail!(
+                "either `num_blocks` or `target_relative_error` must be passed on the command line or set in the scenario"
+            );
+        }
+        Ok(())
+    }
+
+    /// Returns the names of the fields which are set in the scenario.
+    fn set_fields(&self) -> Vec<&'static str> {
+        let mut fields = vec![];
+        macro_rules! push_if_set {
+            ($($field:ident),* $(,)?) => {
+                $(
+                    if self.$field.is_some() {
+                        fields.push(stringify!($field));
+                    }
+                )*
+            };
+        }
+        push_if_set!(
+            num_blocks,
+            target_relative_error,
+            max_blocks,
+            num_shards,
+            seats_per_shard,
+            stake_per_seat,
+            max_malicious_stake_per_shard,
+            liveness_threshold,
+            safety_threshold,
+            corruption_metric,
+            validator_data,
+            mark_malicious,
+            adversary_budget,
+            include_partial_seats,
+            seed,
+            rng,
+            replay_file,
+            threads,
+            importance_sampling,
+            histogram_buckets,
+            histogram_output,
+            histogram_format,
+            output,
+            format,
+            checkpoint,
+            resume,
+        );
+        fields
+    }
+}
+
+/// Returns whether the field `id` of [`REQUIRES`] is present in `config`.
+fn is_present(config: &Config, id: &str) -> bool {
+    match id {
+        "target_relative_error" => config.target_relative_error.is_some(),
+        "max_blocks" => config.max_blocks.is_some(),
+        "histogram_buckets" => config.histogram_buckets.is_some(),
+        "histogram_output" => config.histogram_output.is_some(),
+        "resume" => config.resume,
+        "checkpoint" => config.checkpoint.is_some(),
+        "rng = replay" => config.rng == RngBackend::Replay,
+        "replay_file" => config.replay_file.is_some(),
+        _ => unreachable!("unknown field `{id}`"),
+    }
+}
+
+/// A scalar value of a scenario file which is parsed from its string representation.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Scalar {
+    Integer(u64),
+    String(String),
+}
+
+impl Display for Scalar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Integer(value) => write!(f, "{value}"),
+            Self::String(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+fn parse_scalar<T, E: Error>(scalar: &Scalar) -> Result<T, E>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let value = scalar.to_string();
+    value
+        .parse()
+        .map_err(|err| E::custom(format!("invalid value `{value}`: {err}")))
+}
+
+fn parse<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    parse_scalar(&Scalar::deserialize(deserializer)?).map(Some)
+}
+
+fn parse_list<'de, D, T>(deserializer: D) -> Result<Option<Vec<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    Vec::<Scalar>::deserialize(deserializer)?
+        .iter()
+        .map(parse_scalar)
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
+
+fn parse_value_enum<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: ValueEnum,
+{
+    let value = String::deserialize(deserializer)?;
+    T::from_str(&value, false)
+        .map(Some)
+        .map_err(|err| D::Error::custom(format!("invalid value `{value}`: {err}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{Args, Command, FromArgMatches};
+    use num_rational::Ratio;
+    use std::fs::write;
+    use std::path::Path;
+
+    use super::apply_scenario;
+    use crate::config::Config;
+    use crate::seat_price::StakePerSeat;
+
+    fn parse_run_args(args: &[&str]) -> anyhow::Result<Config> {
+        let matches = Config::augment_args(Command::new("run")).try_get_matches_from(args)?;
+        let mut config = Config::from_arg_matches(&matches)?;
+        apply_scenario(&mut config, &matches)?;
+        Ok(config)
+    }
+
+    fn write_scenario(file_name: &str, content: &str) -> String {
+        let file_path = std::env::temp_dir().join(file_name);
+        write(&file_path, content).unwrap();
+        format!("--config={}", file_path.display())
+    }
+
+    #[test]
+    fn test_apply_scenario() {
+        let config_arg = write_scenario(
+            "sim_validator_assignment_scenario.toml",
+            r#"
+num_blocks = 1000
+num_shards = 4
+seats_per_shard = 68
+stake_per_seat = "1140000000000000000000000000000"
+max_malicious_stake_per_shard = ["1/3", "2/3"]
+include_partial_seats = true
+"#,
+        );
+
+        let config = parse_run_args(&["run", &config_arg]).unwrap();
+        assert_eq!(config.num_blocks, Some(1_000));
+        assert_eq!(config.num_shards, 4);
+        assert_eq!(config.seats_per_shard, 68);
+        assert_eq!(
+            config.stake_per_seat,
+            StakePerSeat::Fixed(1_140_000_000_000_000_000_000_000_000_000)
+        );
+        assert_eq!(
+            config.max_malicious_stake_per_shard,
+            vec![Ratio::new(1, 3), Ratio::new(2, 3)]
+        );
+        assert!(config.include_partial_seats);
+        assert_eq!(config.threads, 1);
+
+        // Arguments passed on the command line override the scenario, including arguments which
+        // conflict with fields of the scenario and booleans which are `true` in the scenario.
+        let config = parse_run_args(&[
+            "run",
+            &config_arg,
+            "--num-shards",
+            "8",
+            "--target-relative-error",
+            "0.5",
+            "--max-blocks",
+            "100",
+            "--include-partial-seats=false",
+        ])
+        .unwrap();
+        assert_eq!(config.num_shards, 8);
+        assert_eq!(config.num_blocks, None);
+        assert_eq!(config.target_relative_error, Some(0.5));
+        assert_eq!(config.max_blocks, Some(100));
+        assert!(!config.include_partial_seats);
+        assert_eq!(config.seats_per_shard, 68);
+    }
+
+    #[test]
+    fn test_apply_scenario_errors() {
+        let config_arg = write_scenario(
+            "sim_validator_assignment_scenario_incomplete.json",
+            r#"{"num_blocks": 1000, "num_shards": 4}"#,
+        );
+        // Required arguments must be set by either the command line or the scenario.
+        assert!(parse_run_args(&["run", &config_arg]).is_err());
+        parse_run_args(&[
+            "run",
+            &config_arg,
+            "--seats-per-shard",
+            "2",
+            "--stake-per-seat",
+            "100",
+            "--max-malicious-stake-per-shard",
+            "1/3",
+        ])
+        .unwrap();
+
+        // Fields of the scenario must satisfy the requirements of the corresponding arguments.
+        let required_args = [
+            "--seats-per-shard",
+            "2",
+            "--stake-per-seat",
+            "100",
+            "--max-malicious-stake-per-shard",
+            "1/3",
+        ];
+        for (file_name, content) in [
+            ("target_relative_error", "target_relative_error = 0.1"),
+            (
+                "histogram_buckets",
+                "num_blocks = 10\nhistogram_buckets = 10",
+            ),
+            ("resume", "num_blocks = 10\nresume = true"),
+            ("rng", "num_blocks = 10\nrng = \"replay\""),
+        ] {
+            let config_arg = write_scenario(
+                &format!("sim_validator_assignment_scenario_{file_name}.toml"),
+                &format!("num_shards = 4\n{content}"),
+            );
+            let args = [&["run", config_arg.as_str()], required_args.as_slice()].concat();
+            let err = parse_run_args(&args).unwrap_err();
+            assert!(err.to_string().contains("requires"), "{err}");
+        }
+        let config_arg = write_scenario(
+            "sim_validator_assignment_scenario_requires.toml",
+            "num_shards = 4\ntarget_relative_error = 0.1\nmax_blocks = 100",
+        );
+        let config =
+            parse_run_args(&[&["run", config_arg.as_str()], required_args.as_slice()].concat())
+                .unwrap();
+        assert_eq!(config.max_blocks, Some(100));
+
+        let config_arg = write_scenario(
+            "sim_validator_assignment_scenario_unknown.toml",
+            "num_shard = 4",
+        );
+        assert!(parse_run_args(&["run", &config_arg]).is_err());
+
+        // Without a scenario, required arguments must be passed on the command line.
+        assert!(parse_run_args(&["run", "--num-blocks", "10"]).is_err());
+        assert!(super::Scenario::read(Path::new("does_not_exist.toml")).is_err());
+    }
+}
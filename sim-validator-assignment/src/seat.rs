@@ -1,5 +1,6 @@
 use serde::Serialize;
 
+use crate::rng::Shuffle;
 use crate::validator::Validator;
 
 /// Represents a seat filled by a particular validator. A seat may not outlive the validator it is
@@ -20,21 +21,27 @@ impl<'validator> Seat<'validator> {
     pub fn get_is_malicious(&self) -> bool {
         self.validator.get_is_malicious()
     }
+
+    pub fn get_account_id(&self) -> &'validator str {
+        self.validator.get_account_id()
+    }
 }
 
-pub struct ShuffledSeats<'seats> {
-    seats: &'seats mut [Seat<'seats>],
+pub struct ShuffledSeats<'seats, 'validator> {
+    seats: &'seats [Seat<'validator>],
 }
 
-impl<'seats> ShuffledSeats<'seats> {
-    /// Shuffles the input `seats`.
-    // TODO(rand) do all shuffling operations in one generic fn
-    pub fn new(seats: &'seats mut [Seat<'seats>]) -> Self {
-        fastrand::shuffle(seats);
+impl<'seats, 'validator> ShuffledSeats<'seats, 'validator> {
+    /// Shuffles the input `seats` in place using `rng`.
+    ///
+    /// Shuffling a permutation of seats again yields a uniformly random permutation, hence the
+    /// same buffer of seats may be reshuffled for every block.
+    pub fn new(seats: &'seats mut [Seat<'validator>], rng: &mut impl Shuffle) -> Self {
+        rng.shuffle(seats);
         Self { seats }
     }
 
-    pub fn get_seats(&self) -> &[Seat] {
+    pub fn get_seats(&self) -> &'seats [Seat<'validator>] {
         self.seats
     }
 }
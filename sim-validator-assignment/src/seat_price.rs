@@ -0,0 +1,159 @@
+use serde::{Serialize, Serializer};
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::validator::RawValidatorData;
+
+/// The amount of stake required to get one seat, either given explicitly or computed from
+/// validator data with [`find_seat_price`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StakePerSeat {
+    Auto,
+    Fixed(u128),
+}
+
+impl StakePerSeat {
+    /// Returns the fixed amount of stake per seat.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stake per seat is [`StakePerSeat::Auto`] and has not been resolved yet.
+    pub fn get(&self) -> u128 {
+        match self {
+            Self::Auto => panic!("stake per seat should be resolved before it is used"),
+            Self::Fixed(stake_per_seat) => *stake_per_seat,
+        }
+    }
+
+    /// Returns the fixed stake per seat or, if it is [`StakePerSeat::Auto`], computes it such that
+    /// the stake of validators in `raw_validator_data` covers `num_seats` seats.
+    pub fn resolve(
+        &self,
+        raw_validator_data: &[RawValidatorData],
+        num_seats: u64,
+    ) -> anyhow::Result<u128> {
+        match self {
+            Self::Auto => {
+                let stakes: Vec<u128> = raw_validator_data.iter().map(|v| v.stake).collect();
+                find_seat_price(&stakes, num_seats)
+            }
+            Self::Fixed(stake_per_seat) => Ok(*stake_per_seat),
+        }
+    }
+}
+
+impl FromStr for StakePerSeat {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            _ => s.parse().map(Self::Fixed),
+        }
+    }
+}
+
+impl Display for StakePerSeat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Auto => write!(f, "auto"),
+            Self::Fixed(stake_per_seat) => write!(f, "{stake_per_seat}"),
+        }
+    }
+}
+
+impl Serialize for StakePerSeat {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Auto => serializer.serialize_str("auto"),
+            Self::Fixed(stake_per_seat) => serializer.serialize_u128(*stake_per_seat),
+        }
+    }
+}
+
+/// Returns the largest stake per seat for which `stakes` cover at least `num_seats` seats.
+///
+/// This follows the seat price algorithm of the NEAR protocol (`find_threshold` in nearcore),
+/// which binary searches the seat price in `[1, sum(stakes)]`.
+pub fn find_seat_price(stakes: &[u128], num_seats: u64) -> anyhow::Result<u128> {
+    let stake_sum = stakes
+        .iter()
+        .try_fold(0u128, |sum, &stake| sum.checked_add(stake))
+        .ok_or_else(|| anyhow::anyhow!("the sum of stakes overflows u128"))?;
+    if num_seats == 0 {
+        anyhow::bail!("at least one seat is required to find the seat price");
+    }
+    if stake_sum < u128::from(num_seats) {
+        anyhow::bail!("the sum of stakes {stake_sum} is too small to cover {num_seats} seats");
+    }
+
+    let covers = |seat_price: u128| {
+        let mut num_covered_seats: u128 = 0;
+        stakes.iter().any(|stake| {
+            num_covered_seats += stake / seat_price;
+            num_covered_seats >= u128::from(num_seats)
+        })
+    };
+    // The upper bound is `stake_sum` instead of `stake_sum + 1` to avoid overflowing, hence it is
+    // checked separately.
+    if covers(stake_sum) {
+        return Ok(stake_sum);
+    }
+
+    // Invariant: `left` covers `num_seats` seats and `right` does not.
+    let (mut left, mut right) = (1, stake_sum);
+    while left < right - 1 {
+        let mid = left + (right - left) / 2;
+        if covers(mid) {
+            left = mid;
+        } else {
+            right = mid;
+        }
+    }
+    Ok(left)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_seat_price, StakePerSeat};
+
+    #[test]
+    fn test_find_seat_price() {
+        assert_eq!(
+            find_seat_price(&[1_000_000, 1_000_000, 10], 10).unwrap(),
+            200_000
+        );
+        assert_eq!(
+            find_seat_price(&[1_000_000, 1_000_000], 1).unwrap(),
+            1_000_000
+        );
+        assert_eq!(
+            find_seat_price(&[1_000_000, 1_000_000], 3).unwrap(),
+            500_000
+        );
+        assert_eq!(find_seat_price(&[1, 1, 2], 4).unwrap(), 1);
+        assert!(find_seat_price(&[1, 1, 2], 5).is_err());
+        assert!(find_seat_price(&[1, 1, 2], 0).is_err());
+        assert_eq!(find_seat_price(&[u128::MAX], 1).unwrap(), u128::MAX);
+        assert_eq!(
+            find_seat_price(&[u128::MAX - 1, 1], 1).unwrap(),
+            u128::MAX - 1
+        );
+        assert_eq!(
+            find_seat_price(&[u128::MAX - 1, 1], 2).unwrap(),
+            u128::MAX / 2
+        );
+    }
+
+    #[test]
+    fn test_parse_stake_per_seat() {
+        assert_eq!("auto".parse::<StakePerSeat>().unwrap(), StakePerSeat::Auto);
+        assert_eq!(
+            "1140000000000000000000000000000"
+                .parse::<StakePerSeat>()
+                .unwrap(),
+            StakePerSeat::Fixed(1_140_000_000_000_000_000_000_000_000_000)
+        );
+        assert!("-1".parse::<StakePerSeat>().is_err());
+    }
+}
@@ -6,16 +6,23 @@ use std::path::PathBuf;
 use crate::{
     config::seats_per_stake,
     partial_seat::PartialSeat,
+    seat_price::StakePerSeat,
+    validation::validate_seat_stats_config,
     validator::{
         new_ordered_partial_seats, parse_raw_validator_data, read_validator_data, Validator,
     },
 };
 
 #[derive(Args, Debug)]
-pub(crate) struct SeatStatsConfig {
-    /// The amount of stake required to get one seat.
+pub struct SeatStatsConfig {
+    /// The amount of stake required to get one seat. Pass `auto` to compute it from validator data
+    /// such that validators cover `num_seats` seats, following the seat price algorithm of the
+    /// NEAR protocol.
     #[arg(long)]
-    pub stake_per_seat: u128,
+    pub stake_per_seat: StakePerSeat,
+    /// The number of seats to fill, required if `stake_per_seat` is `auto`.
+    #[arg(long, required_if_eq("stake_per_seat", "auto"))]
+    pub num_seats: Option<u64>,
     /// The file from which validator data is read. It is expected to contain a vector of
     /// `RawValidatorData` serialized as JSON.
     #[arg(long)]
@@ -25,10 +32,16 @@ pub(crate) struct SeatStatsConfig {
     pub include_partial_seats: bool,
 }
 
-pub(crate) fn print_seat_stats(config: &SeatStatsConfig) -> anyhow::Result<()> {
+pub fn print_seat_stats(config: &SeatStatsConfig) -> anyhow::Result<()> {
     let raw_validator_data = read_validator_data(config.validator_data.as_path())?;
+    validate_seat_stats_config(config, &raw_validator_data)?;
+    let stake_per_seat = config
+        .stake_per_seat
+        .resolve(&raw_validator_data, config.num_seats.unwrap_or_default())?;
     let (population_stats, validators) =
-        parse_raw_validator_data(&raw_validator_data, config.stake_per_seat);
+        parse_raw_validator_data(&raw_validator_data, stake_per_seat)?;
+
+    println!("stake_per_seat\t{stake_per_seat}");
 
     println!(
         "malicious_stake/stake\t{:.4}",
@@ -46,14 +59,14 @@ pub(crate) fn print_seat_stats(config: &SeatStatsConfig) -> anyhow::Result<()> {
     );
 
     if config.include_partial_seats {
-        print_partial_seat_stats(&config, &validators);
+        print_partial_seat_stats(stake_per_seat, &validators);
     }
 
     Ok(())
 }
 
-fn print_partial_seat_stats(config: &SeatStatsConfig, validators: &[Validator]) {
-    let partial_seats = new_ordered_partial_seats(validators, config.stake_per_seat);
+fn print_partial_seat_stats(stake_per_seat: u128, validators: &[Validator]) {
+    let partial_seats = new_ordered_partial_seats(validators, stake_per_seat);
     let malicious_partial_seats = partial_seats
         .iter()
         .filter(|ps| ps.get_is_malicious())
@@ -69,11 +82,11 @@ fn print_partial_seat_stats(config: &SeatStatsConfig, validators: &[Validator])
     );
     println!(
         "equivalent_num_seats\t{}",
-        seats_per_stake(sum_weights, config.stake_per_seat)
+        seats_per_stake(sum_weights, stake_per_seat)
     );
     println!(
         "equivalent_num_malicious_seats\t{}",
-        seats_per_stake(sum_malicious_weights, config.stake_per_seat)
+        seats_per_stake(sum_malicious_weights, stake_per_seat)
     );
 }
 
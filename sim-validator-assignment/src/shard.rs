@@ -1,56 +1,320 @@
+use crate::compact::{CompactPopulation, ValidatorSet};
 use crate::config::Config;
 use crate::partial_seat::PartialSeat;
 use crate::seat::Seat;
+use clap::ValueEnum;
 use num_rational::Ratio;
+use serde::Serialize;
 
-#[derive(Debug, Default)]
-pub struct Shard<'seats> {
-    seats: Vec<&'seats Seat<'seats>>,
-    partial_seats: Vec<&'seats PartialSeat<'seats>>,
-    stake: u128,
-    malicious_stake: u128,
+/// Classifies a shard by the failures its malicious stake can cause in a BFT protocol.
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ShardStatus {
+    /// Malicious stake does not exceed the liveness threshold.
+    Healthy,
+    /// Malicious stake exceeds the liveness threshold, hence it can stall the shard, but does not
+    /// exceed the safety threshold.
+    LivenessCompromised,
+    /// Malicious stake exceeds the safety threshold, hence it can finalize invalid state.
+    SafetyCompromised,
 }
 
-impl<'seats> Shard<'seats> {
-    pub fn new(
+/// Determines how the malicious share of a shard is measured.
+#[derive(ValueEnum, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CorruptionMetric {
+    /// The ratio of malicious stake, including the weights of partial seats.
+    Stake,
+    /// The ratio of malicious seats. Every partial seat counts as one seat.
+    Seats,
+    /// The ratio of malicious validators, counting every validator holding (partial) seats in the
+    /// shard once.
+    Validators,
+}
+
+/// Tallies the weight assigned to a shard, as measured by [`CorruptionMetric`].
+///
+/// Only sums are stored (instead of references to the shard's seats), so constructing a shard from
+/// compact seats requires no heap allocation.
+#[derive(PartialEq, Debug, Default)]
+pub struct Shard {
+    weight: u128,
+    malicious_weight: u128,
+}
+
+impl Shard {
+    pub fn new<'seats, 'validator: 'seats>(
         config: &Config,
-        seats: Vec<&'seats Seat>,
-        partial_seats: Vec<&'seats PartialSeat>,
+        seats: &[Seat<'validator>],
+        partial_seats: impl IntoIterator<Item = &'seats PartialSeat<'validator>>,
     ) -> anyhow::Result<Self> {
-        if seats.len() != usize::try_from(config.seats_per_shard).unwrap() {
-            // Count only _full_ seats for the minimum number of required seats, since it is not
-            // clear how a _partial_ seat should be weighted for that concern.
-            // Validator assignment frameworks might try to minimize the number of partial seats or
-            // try to ignore them entirely.
-            anyhow::bail!(
-                "Shard requires {} seats, received {} seats",
-                config.seats_per_shard,
-                seats.len()
-            )
-        }
+        check_num_seats(config, seats.len())?;
 
         let mut shard = Self::default();
-        for s in seats.iter() {
-            shard.stake += config.stake_per_seat;
-            if s.get_is_malicious() {
-                shard.malicious_stake += config.stake_per_seat;
+        match config.corruption_metric {
+            CorruptionMetric::Stake => {
+                for s in seats.iter() {
+                    shard.add(config.stake_per_seat.get(), s.get_is_malicious());
+                }
+                for ps in partial_seats {
+                    shard.add(ps.get_weight(), ps.get_is_malicious());
+                }
+            }
+            CorruptionMetric::Seats => {
+                for s in seats.iter() {
+                    shard.add(1, s.get_is_malicious());
+                }
+                for ps in partial_seats {
+                    shard.add(1, ps.get_is_malicious());
+                }
+            }
+            CorruptionMetric::Validators => {
+                let mut validators: Vec<(&str, bool)> = seats
+                    .iter()
+                    .map(|s| (s.get_account_id(), s.get_is_malicious()))
+                    .chain(
+                        partial_seats
+                            .into_iter()
+                            .map(|ps| (ps.get_account_id(), ps.get_is_malicious())),
+                    )
+                    .collect();
+                validators.sort_unstable();
+                validators.dedup();
+                for (_, is_malicious) in validators {
+                    shard.add(1, is_malicious);
+                }
             }
         }
 
-        for ps in partial_seats.iter() {
-            let weight = ps.get_weight();
-            shard.stake += weight;
-            if ps.get_is_malicious() {
-                shard.malicious_stake += weight;
+        Ok(shard)
+    }
+
+    /// Constructs a shard from seats in compact form, i.e. seats represented by the index of the
+    /// validator holding them in `population`.
+    ///
+    /// `distinct_validators` must be empty. It is used to count distinct validators for
+    /// [`CorruptionMetric::Validators`] and is empty again when this function returns, so callers
+    /// can reuse it for every shard.
+    pub fn from_compact<'seats, P>(
+        config: &Config,
+        population: &CompactPopulation,
+        seats: &'seats [u32],
+        partial_seats: P,
+        distinct_validators: &mut ValidatorSet,
+    ) -> anyhow::Result<Self>
+    where
+        P: IntoIterator<Item = &'seats u32>,
+        P::IntoIter: Clone,
+    {
+        check_num_seats(config, seats.len())?;
+
+        let num_seats = u128::try_from(seats.len()).expect("number of seats should fit u128");
+        let num_malicious_seats = u128::from(population.count_malicious(seats));
+        let mut shard = Self::default();
+        match config.corruption_metric {
+            CorruptionMetric::Stake => {
+                let stake_per_seat = population.get_stake_per_seat();
+                shard.weight = num_seats * stake_per_seat;
+                shard.malicious_weight = num_malicious_seats * stake_per_seat;
+                for &validator_idx in partial_seats {
+                    shard.add(
+                        population.partial_seat_weight(validator_idx),
+                        population.is_malicious(validator_idx),
+                    );
+                }
+            }
+            CorruptionMetric::Seats => {
+                shard.weight = num_seats;
+                shard.malicious_weight = num_malicious_seats;
+                for &validator_idx in partial_seats {
+                    shard.add(1, population.is_malicious(validator_idx));
+                }
+            }
+            CorruptionMetric::Validators => {
+                let validators = seats.iter().chain(partial_seats);
+                for &validator_idx in validators.clone() {
+                    if distinct_validators.insert(validator_idx) {
+                        shard.add(1, population.is_malicious(validator_idx));
+                    }
+                }
+                for &validator_idx in validators {
+                    distinct_validators.remove(validator_idx);
+                }
             }
         }
 
-        shard.seats = seats;
-        shard.partial_seats = partial_seats;
         Ok(shard)
     }
 
-    pub fn is_corrupted(&self, config: &Config) -> bool {
-        Ratio::new(self.malicious_stake, self.stake) > config.max_malicious_stake_per_shard
+    /// Returns `malicious_weight / weight` of the shard, e.g. `malicious_stake / stake` for
+    /// [`CorruptionMetric::Stake`].
+    pub fn malicious_ratio(&self) -> Ratio<u128> {
+        Ratio::new(self.malicious_weight, self.weight)
+    }
+
+    /// Returns whether the malicious ratio exceeds `max_malicious_stake_per_shard`.
+    pub fn is_corrupted(&self, max_malicious_stake_per_shard: Ratio<u128>) -> bool {
+        self.malicious_ratio() > max_malicious_stake_per_shard
+    }
+
+    /// Classifies the shard with respect to `liveness_threshold` and `safety_threshold`, which are
+    /// expected to satisfy `liveness_threshold <= safety_threshold`.
+    pub fn status(
+        &self,
+        liveness_threshold: Ratio<u128>,
+        safety_threshold: Ratio<u128>,
+    ) -> ShardStatus {
+        if self.is_corrupted(safety_threshold) {
+            ShardStatus::SafetyCompromised
+        } else if self.is_corrupted(liveness_threshold) {
+            ShardStatus::LivenessCompromised
+        } else {
+            ShardStatus::Healthy
+        }
+    }
+
+    fn add(&mut self, weight: u128, is_malicious: bool) {
+        self.weight += weight;
+        if is_malicious {
+            self.malicious_weight += weight;
+        }
+    }
+}
+
+fn check_num_seats(config: &Config, num_seats: usize) -> anyhow::Result<()> {
+    if num_seats != usize::try_from(config.seats_per_shard).unwrap() {
+        // Count only _full_ seats for the minimum number of required seats, since it is not
+        // clear how a _partial_ seat should be weighted for that concern.
+        // Validator assignment frameworks might try to minimize the number of partial seats or
+        // try to ignore them entirely.
+        anyhow::bail!(
+            "Shard requires {} seats, received {} seats",
+            config.seats_per_shard,
+            num_seats
+        )
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use num_rational::Ratio;
+
+    use super::{CorruptionMetric, Shard, ShardStatus};
+    use crate::compact::{CompactPopulation, ValidatorSet};
+    use crate::config::Config;
+    use crate::seat_price::StakePerSeat;
+    use crate::validator::tests::new_test_raw_validator_data;
+    use crate::validator::{
+        new_ordered_partial_seats, new_ordered_seats, parse_raw_validator_data,
+    };
+
+    #[test]
+    fn test_from_compact_matches_new() {
+        for corruption_metric in [
+            CorruptionMetric::Stake,
+            CorruptionMetric::Seats,
+            CorruptionMetric::Validators,
+        ] {
+            let mut config = Config::new_mock(true);
+            config.stake_per_seat = StakePerSeat::Fixed(90);
+            config.corruption_metric = corruption_metric;
+            let (_, validators) = parse_raw_validator_data(
+                &new_test_raw_validator_data(),
+                config.stake_per_seat.get(),
+            )
+            .unwrap();
+            let seats = new_ordered_seats(&validators);
+            let partial_seats = new_ordered_partial_seats(&validators, config.stake_per_seat.get());
+            let population =
+                CompactPopulation::new(&validators, config.stake_per_seat.get()).unwrap();
+            let compact_seats = population.new_ordered_seats();
+            let compact_partial_seats = population.new_ordered_partial_seats();
+            let mut distinct_validators = ValidatorSet::new(&population);
+
+            for shard_idx in 0..usize::from(config.num_shards) {
+                let shard = Shard::new(
+                    &config,
+                    config.collect_seats_for_shard(shard_idx, &seats).unwrap(),
+                    config
+                        .collect_partial_seats_for_shard(shard_idx, &partial_seats)
+                        .unwrap(),
+                )
+                .unwrap();
+                let compact_shard = Shard::from_compact(
+                    &config,
+                    &population,
+                    config
+                        .collect_seats_for_shard(shard_idx, &compact_seats)
+                        .unwrap(),
+                    config
+                        .collect_partial_seats_for_shard(shard_idx, &compact_partial_seats)
+                        .unwrap(),
+                    &mut distinct_validators,
+                )
+                .unwrap();
+                assert_eq!(shard, compact_shard);
+            }
+        }
+    }
+
+    #[test]
+    fn test_corruption_metrics() {
+        let mut config = Config::new_mock(true);
+        config.stake_per_seat = StakePerSeat::Fixed(90);
+        let (_, validators) =
+            parse_raw_validator_data(&new_test_raw_validator_data(), config.stake_per_seat.get())
+                .unwrap();
+        let population = CompactPopulation::new(&validators, config.stake_per_seat.get()).unwrap();
+        // One seat of an honest and one seat of a malicious validator, and two partial seats of
+        // the malicious validator.
+        let seats = population.new_ordered_seats();
+        let shard_seats = [seats[0], *seats.iter().find(|&&idx| idx == 1).unwrap()];
+        let shard_partial_seats = [1, 1];
+        let mut distinct_validators = ValidatorSet::new(&population);
+
+        let mut ratios = vec![];
+        for corruption_metric in [
+            CorruptionMetric::Stake,
+            CorruptionMetric::Seats,
+            CorruptionMetric::Validators,
+        ] {
+            config.corruption_metric = corruption_metric;
+            let shard = Shard::from_compact(
+                &config,
+                &population,
+                &shard_seats,
+                &shard_partial_seats,
+                &mut distinct_validators,
+            )
+            .unwrap();
+            ratios.push(shard.malicious_ratio());
+        }
+        let partial_seat_weight = population.partial_seat_weight(1);
+        assert_eq!(
+            ratios,
+            vec![
+                Ratio::new(90 + 2 * partial_seat_weight, 180 + 2 * partial_seat_weight),
+                Ratio::new(3, 4),
+                Ratio::new(1, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_status() {
+        let liveness_threshold = Ratio::new(1, 3);
+        let safety_threshold = Ratio::new(2, 3);
+        for (malicious_stake, expected) in [
+            (0, ShardStatus::Healthy),
+            (100, ShardStatus::Healthy),
+            (101, ShardStatus::LivenessCompromised),
+            (200, ShardStatus::LivenessCompromised),
+            (201, ShardStatus::SafetyCompromised),
+        ] {
+            let shard = Shard {
+                weight: 300,
+                malicious_weight: malicious_stake,
+            };
+            assert_eq!(shard.status(liveness_threshold, safety_threshold), expected);
+        }
     }
 }
@@ -0,0 +1,91 @@
+use serde::Serialize;
+
+/// The quantile of the standard normal distribution for a two-sided 95% confidence interval.
+pub const Z_95: f64 = 1.959_963_984_540_054;
+
+/// A confidence interval for a probability.
+#[derive(Serialize, PartialEq, Debug, Clone, Copy)]
+pub struct ConfidenceInterval {
+    pub lower: f64,
+    pub upper: f64,
+}
+
+impl ConfidenceInterval {
+    /// Returns the Wilson score interval for observing `successes` in `trials` Bernoulli trials.
+    ///
+    /// Unlike the normal approximation, it is well behaved for probabilities close to 0, which is
+    /// the regime of shard corruption.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `trials` is 0.
+    pub fn wilson(successes: u64, trials: u64, z: f64) -> Self {
+        assert!(trials > 0, "trials should be greater than zero");
+        let n = trials as f64;
+        let p = successes as f64 / n;
+        let z2 = z * z;
+        let denominator = 1.0 + z2 / n;
+        let center = (p + z2 / (2.0 * n)) / denominator;
+        let half_width = z * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt() / denominator;
+        Self {
+            // Rounding errors would yield a tiny positive lower bound for `successes == 0`.
+            lower: if successes == 0 {
+                0.0
+            } else {
+                (center - half_width).max(0.0)
+            },
+            upper: (center + half_width).min(1.0),
+        }
+    }
+
+    /// Returns the interval `estimate ± z * std_error`, based on the normal approximation.
+    pub fn normal(estimate: f64, std_error: f64, z: f64) -> Self {
+        Self {
+            lower: (estimate - z * std_error).max(0.0),
+            upper: estimate + z * std_error,
+        }
+    }
+
+    /// Returns half of the interval's width relative to `estimate`. It is infinite if `estimate`
+    /// is 0, since nothing is known about the order of magnitude of the probability.
+    pub fn relative_error(&self, estimate: f64) -> f64 {
+        if estimate == 0.0 {
+            return f64::INFINITY;
+        }
+        (self.upper - self.lower) / 2.0 / estimate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConfidenceInterval, Z_95};
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn test_wilson() {
+        let interval = ConfidenceInterval::wilson(10, 100, Z_95);
+        assert_close(interval.lower, 0.055_229);
+        assert_close(interval.upper, 0.174_366);
+
+        // No successes still yield an upper bound.
+        let interval = ConfidenceInterval::wilson(0, 1_000, Z_95);
+        assert_eq!(interval.lower, 0.0);
+        assert_close(interval.upper, 0.003_827);
+    }
+
+    #[test]
+    fn test_relative_error() {
+        let interval = ConfidenceInterval {
+            lower: 0.08,
+            upper: 0.12,
+        };
+        assert_close(interval.relative_error(0.1), 0.2);
+        assert_eq!(interval.relative_error(0.0), f64::INFINITY);
+    }
+}
@@ -0,0 +1,379 @@
+use clap::Args;
+use num_rational::Ratio;
+use num_traits::{CheckedAdd, One, Zero};
+use serde::Serialize;
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::compact::CompactPopulation;
+use crate::config::Config;
+use crate::counters::{Counters, ProbabilityEstimate};
+use crate::output::{print_table, write_records, OutputFormat, TableRow};
+use crate::rng::{RngBackend, SimRng};
+use crate::run::{log_seed, mock_validator_data, seed_or_random, simulate_batch, simulate_blocks};
+use crate::seat_price::StakePerSeat;
+use crate::shard::CorruptionMetric;
+use crate::validation::validate_config;
+use crate::validator::{parse_raw_validator_data, read_validator_data};
+
+#[derive(Args, Debug)]
+pub struct SweepConfig {
+    /// The number of blocks to simulate for every combination of parameters.
+    #[arg(long)]
+    pub num_blocks: u64,
+    /// The numbers of shards to sweep. Values are separated by commas and ranges may be given as
+    /// `start..=end` or `start..=end:step`, e.g. `4,8..=32:8`.
+    #[arg(long)]
+    pub num_shards: ValueList<u16>,
+    /// The numbers of seats per shard to sweep, in the same syntax as `num_shards`.
+    #[arg(long)]
+    pub seats_per_shard: ValueList<u64>,
+    /// The amounts of stake required to get one seat to sweep, in the same syntax as `num_shards`.
+    #[arg(long)]
+    pub stake_per_seat: ValueList<u128>,
+    /// The thresholds of malicious stake above which a shard is considered corrupted, separated by
+    /// commas. All thresholds are evaluated for the same shuffles of seats.
+    #[arg(long, value_delimiter = ',', required = true)]
+    pub max_malicious_stake_per_shard: Vec<Ratio<u128>>,
+    /// The file from which validator data is read. It is expected to contain a vector of
+    /// `RawValidatorData` serialized as JSON. If no validator data is provided, mocked validator
+    /// data will be used.
+    #[arg(long)]
+    pub validator_data: Option<PathBuf>,
+    #[command(flatten)]
+    pub args: SimulationArgs,
+}
+
+/// The arguments shared by the commands which simulate several configurations, such as `sweep` or
+/// `tolerance`.
+#[derive(Args, Clone, Debug)]
+pub struct SimulationArgs {
+    #[arg(long, default_value_t = false)]
+    pub include_partial_seats: bool,
+    #[arg(long, value_enum, default_value_t = CorruptionMetric::Stake)]
+    pub corruption_metric: CorruptionMetric,
+    /// The seed of the random number generator. Every configuration is simulated with rngs seeded
+    /// by it.
+    #[arg(long)]
+    pub seed: Option<u64>,
+    #[arg(long, default_value_t = 1)]
+    pub threads: u16,
+    /// The file to which the result table is written.
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+    /// The format in which the result table is written to `output`.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Json)]
+    pub format: OutputFormat,
+}
+
+impl SimulationArgs {
+    #[cfg(test)]
+    pub fn default_for_test() -> Self {
+        Self {
+            include_partial_seats: false,
+            corruption_metric: CorruptionMetric::Stake,
+            seed: None,
+            threads: 1,
+            output: None,
+            format: OutputFormat::Json,
+        }
+    }
+
+    /// Returns the config to simulate `num_blocks` blocks of one configuration. Options which are
+    /// specific to `run`, e.g. importance sampling, are disabled.
+    pub fn run_config(
+        &self,
+        num_blocks: u64,
+        num_shards: u16,
+        seats_per_shard: u64,
+        stake_per_seat: StakePerSeat,
+        max_malicious_stake_per_shard: Vec<Ratio<u128>>,
+    ) -> Config {
+        Config {
+            config: None,
+            num_blocks: Some(num_blocks),
+            target_relative_error: None,
+            max_blocks: None,
+            num_shards,
+            seats_per_shard,
+            stake_per_seat,
+            max_malicious_stake_per_shard,
+            liveness_threshold: Ratio::new(1, 3),
+            safety_threshold: Ratio::new(2, 3),
+            corruption_metric: self.corruption_metric,
+            validator_data: None,
+            mark_malicious: None,
+            adversary_budget: None,
+            include_partial_seats: self.include_partial_seats,
+            seed: self.seed,
+            rng: RngBackend::Fastrand,
+            replay_file: None,
+            threads: self.threads,
+            importance_sampling: false,
+            histogram_buckets: None,
+            histogram_output: None,
+            histogram_format: OutputFormat::Csv,
+            output: None,
+            format: OutputFormat::Json,
+            checkpoint: None,
+            resume: false,
+        }
+    }
+
+    /// Writes `rows` to `output`, if it is given.
+    pub fn write_table<T: Serialize>(&self, rows: &[T]) -> anyhow::Result<()> {
+        if let Some(path) = &self.output {
+            write_records(rows, path, self.format)?;
+            println!("Wrote the result table to {}", path.display());
+        }
+        Ok(())
+    }
+}
+
+/// The estimates of a simulation with respect to one threshold of malicious stake, which form the
+/// last columns of result tables.
+#[derive(Serialize, Debug)]
+pub struct ThresholdEstimates {
+    pub max_malicious_stake_per_shard: Ratio<u128>,
+    pub num_blocks: u64,
+    pub num_corrupted_shards: u64,
+    pub shard_corruption: ProbabilityEstimate,
+    pub num_corrupted_blocks: u64,
+    pub block_corruption: ProbabilityEstimate,
+}
+
+impl ThresholdEstimates {
+    /// The names of the columns of [`Self::cells`].
+    pub const HEADER: &'static str = "max_malicious_stake_per_shard\tcorrupted_shards\tp(shard corrupted)\t95% confidence interval\tcorrupted_blocks\tp(any shard in block corrupted)\t95% confidence interval";
+
+    /// Returns the estimates for every threshold counted by `counters`.
+    pub fn from_counters(counters: &Counters) -> Vec<Self> {
+        counters
+            .thresholds
+            .iter()
+            .enumerate()
+            .map(|(threshold_idx, threshold)| Self {
+                max_malicious_stake_per_shard: threshold.max_malicious_stake_per_shard,
+                num_blocks: counters.num_blocks,
+                num_corrupted_shards: threshold.num_corrupted_shards,
+                shard_corruption: counters.shard_corruption(threshold_idx),
+                num_corrupted_blocks: threshold.num_corrupted_blocks,
+                block_corruption: counters.block_corruption(threshold_idx),
+            })
+            .collect()
+    }
+
+    /// Returns the estimates as tab separated cells of a table row.
+    pub fn cells(&self) -> String {
+        format!(
+            "{}\t{}\t{:.4e}\t[{:.4e}, {:.4e}]\t{}\t{:.4e}\t[{:.4e}, {:.4e}]",
+            self.max_malicious_stake_per_shard,
+            self.num_corrupted_shards,
+            self.shard_corruption.estimate,
+            self.shard_corruption.confidence_interval.lower,
+            self.shard_corruption.confidence_interval.upper,
+            self.num_corrupted_blocks,
+            self.block_corruption.estimate,
+            self.block_corruption.confidence_interval.lower,
+            self.block_corruption.confidence_interval.upper,
+        )
+    }
+}
+
+/// A list of values given by comma separated values and ranges, see [`SweepConfig::num_shards`].
+/// Duplicate values are dropped.
+#[derive(Clone, Debug)]
+pub struct ValueList<T>(pub Vec<T>);
+
+impl<T> FromStr for ValueList<T>
+where
+    T: FromStr + CheckedAdd + One + Zero + PartialOrd + Copy,
+    T::Err: Display,
+{
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |value: &str| {
+            value
+                .trim()
+                .parse::<T>()
+                .map_err(|err| anyhow::anyhow!("invalid value `{value}`: {err}"))
+        };
+
+        let mut values = vec![];
+        for item in s.split(',') {
+            let Some((start, rest)) = item.split_once("..=") else {
+                values.push(parse(item)?);
+                continue;
+            };
+            let (end, step) = match rest.split_once(':') {
+                Some((end, step)) => (parse(end)?, parse(step)?),
+                None => (parse(rest)?, T::one()),
+            };
+            if step.is_zero() {
+                anyhow::bail!("step of range `{item}` must not be zero");
+            }
+            let mut value = parse(start)?;
+            while value <= end {
+                values.push(value);
+                match value.checked_add(&step) {
+                    Some(next) => value = next,
+                    None => break,
+                }
+            }
+        }
+
+        let mut unique_values = vec![];
+        for value in values {
+            if !unique_values.contains(&value) {
+                unique_values.push(value);
+            }
+        }
+        if unique_values.is_empty() {
+            anyhow::bail!("`{s}` contains no values");
+        }
+        Ok(Self(unique_values))
+    }
+}
+
+/// A row of the result table of a sweep.
+#[derive(Serialize, Debug)]
+pub struct SweepRow {
+    pub num_shards: u16,
+    pub seats_per_shard: u64,
+    pub stake_per_seat: u128,
+    #[serde(flatten)]
+    pub estimates: ThresholdEstimates,
+}
+
+impl TableRow for SweepRow {
+    fn header() -> String {
+        format!(
+            "num_shards\tseats_per_shard\tstake_per_seat\t{}",
+            ThresholdEstimates::HEADER
+        )
+    }
+
+    fn cells(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}",
+            self.num_shards,
+            self.seats_per_shard,
+            self.stake_per_seat,
+            self.estimates.cells()
+        )
+    }
+}
+
+/// Simulates every combination of `num_shards`, `seats_per_shard` and `stake_per_seat` and prints
+/// one table with a row per combination and threshold.
+///
+/// Validator data is read once and parsed once per value of `stake_per_seat`. Combinations for
+/// which validators do not cover the required seats are skipped.
+pub fn sweep(config: &SweepConfig) -> anyhow::Result<()> {
+    if config.num_blocks == 0 {
+        anyhow::bail!("at least one block is required per combination of parameters");
+    }
+    if config.args.threads == 0 {
+        anyhow::bail!("at least one thread is required to run the simulation");
+    }
+
+    let raw_validator_data = match &config.validator_data {
+        Some(file_path) => read_validator_data(file_path.as_path())?,
+        None => mock_validator_data(),
+    };
+    let seed = seed_or_random(config.args.seed);
+
+    let mut rows = vec![];
+    for &stake_per_seat in config.stake_per_seat.0.iter() {
+        if stake_per_seat == 0 {
+            anyhow::bail!("stake_per_seat must be greater than zero");
+        }
+        let (population_stats, validators) =
+            parse_raw_validator_data(&raw_validator_data, stake_per_seat)?;
+        let population = CompactPopulation::new(&validators, stake_per_seat)?;
+
+        for &num_shards in config.num_shards.0.iter() {
+            for &seats_per_shard in config.seats_per_shard.0.iter() {
+                let run_config = config.args.run_config(
+                    config.num_blocks,
+                    num_shards,
+                    seats_per_shard,
+                    StakePerSeat::Fixed(stake_per_seat),
+                    config.max_malicious_stake_per_shard.clone(),
+                );
+                validate_config(&run_config, &raw_validator_data)?;
+                if population_stats.seats < run_config.total_seats() {
+                    println!(
+                        "Skipping num_shards {num_shards}, seats_per_shard {seats_per_shard}, stake_per_seat {stake_per_seat}: validators cover {} of {} seats",
+                        population_stats.seats,
+                        run_config.total_seats()
+                    );
+                    continue;
+                }
+
+                let counters = simulate_run_config(&run_config, &population, seed)?;
+                println!(
+                    "Simulated num_shards {num_shards}, seats_per_shard {seats_per_shard}, stake_per_seat {stake_per_seat}"
+                );
+
+                rows.extend(
+                    ThresholdEstimates::from_counters(&counters)
+                        .into_iter()
+                        .map(|estimates| SweepRow {
+                            num_shards,
+                            seats_per_shard,
+                            stake_per_seat,
+                            estimates,
+                        }),
+                );
+            }
+        }
+    }
+
+    print_table(&rows);
+    config.args.write_table(&rows)?;
+    log_seed(seed, config.args.threads);
+    Ok(())
+}
+
+/// Simulates `config.num_blocks` blocks with `config.threads` rngs seeded by `seed`.
+pub(crate) fn simulate_run_config(
+    config: &Config,
+    population: &CompactPopulation,
+    seed: u64,
+) -> anyhow::Result<Counters> {
+    let mut rngs = (0..config.threads)
+        .map(|stream| SimRng::new(config.rng, seed, stream.into(), None))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let mut counters = Counters::new(
+        config.num_shards,
+        &config.max_malicious_stake_per_shard,
+        None,
+    );
+    for worker_counters in simulate_batch(&mut rngs, config.block_limit(), |rng, num_blocks| {
+        simulate_blocks(config, population, rng, num_blocks)
+    })? {
+        counters.merge(&worker_counters);
+    }
+    Ok(counters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ValueList;
+
+    #[test]
+    fn test_value_list() {
+        let values: ValueList<u16> = "4,8..=16:4,2..=3,4".parse().unwrap();
+        assert_eq!(values.0, vec![4, 8, 12, 16, 2, 3]);
+
+        let values: ValueList<u8> = "250..=255:3".parse().unwrap();
+        assert_eq!(values.0, vec![250, 253]);
+
+        assert!("1..=4:0".parse::<ValueList<u64>>().is_err());
+        assert!("5..=4".parse::<ValueList<u64>>().is_err());
+        assert!("a".parse::<ValueList<u64>>().is_err());
+    }
+}
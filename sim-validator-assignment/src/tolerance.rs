@@ -0,0 +1,264 @@
+use clap::Args;
+use num_rational::Ratio;
+use num_traits::ToPrimitive;
+use serde::Serialize;
+use std::path::PathBuf;
+
+use crate::malicious::{mark_stake_fraction, StakeStrategy};
+use crate::output::{print_table, TableRow};
+use crate::run::{log_seed, prepare_population, seed_or_random};
+use crate::seat_price::StakePerSeat;
+use crate::sweep::{simulate_run_config, SimulationArgs, ThresholdEstimates, ValueList};
+use crate::validator::{read_validator_data, RawValidatorData};
+
+#[derive(Args, Debug)]
+pub struct ToleranceConfig {
+    /// The number of blocks to simulate for every fraction of malicious stake.
+    #[arg(long)]
+    pub num_blocks: u64,
+    #[arg(long)]
+    pub num_shards: u16,
+    #[arg(long)]
+    pub seats_per_shard: u64,
+    /// The amount of stake required to get one seat. Pass `auto` to compute it from validator data
+    /// such that validators cover `num_shards * seats_per_shard` seats.
+    #[arg(long)]
+    pub stake_per_seat: StakePerSeat,
+    /// The fractions of the total stake held by malicious validators to simulate. Values are
+    /// separated by commas and ranges may be given as `start..=end:step`, e.g. `1/10..=1/2:1/20`.
+    #[arg(long)]
+    pub malicious_stake_fractions: ValueList<Ratio<u128>>,
+    /// The order in which validators are made malicious until they hold a fraction of stake. The
+    /// `random` order is drawn with a seed derived from `--seed`.
+    #[arg(long, value_enum, default_value_t = StakeStrategy::Random)]
+    pub strategy: StakeStrategy,
+    /// A fraction of malicious stake is tolerated if, for it and all smaller fractions, the
+    /// estimated probability that any shard in a block is corrupted does not exceed this value.
+    #[arg(long, default_value_t = 0.0)]
+    pub max_block_corruption: f64,
+    /// The thresholds of malicious stake above which a shard is considered corrupted, separated by
+    /// commas.
+    #[arg(long, value_delimiter = ',', required = true)]
+    pub max_malicious_stake_per_shard: Vec<Ratio<u128>>,
+    /// The file from which validator data is read. It is expected to contain a vector of
+    /// `RawValidatorData` serialized as JSON. Its `is_malicious` values are ignored.
+    #[arg(long)]
+    pub validator_data: PathBuf,
+    #[command(flatten)]
+    pub args: SimulationArgs,
+}
+
+/// A row of the corruption probability curve.
+#[derive(Serialize, Debug)]
+pub struct ToleranceRow {
+    /// The targeted fraction of malicious stake.
+    pub malicious_stake_fraction: Ratio<u128>,
+    /// The fraction of stake actually held by malicious validators, which may exceed the target
+    /// since validators are made malicious as a whole.
+    pub malicious_stake_ratio: f64,
+    pub malicious_seats: u64,
+    pub seats: u64,
+    #[serde(flatten)]
+    pub estimates: ThresholdEstimates,
+}
+
+impl TableRow for ToleranceRow {
+    fn header() -> String {
+        format!(
+            "malicious_stake_fraction\tmalicious_stake/stake\tmalicious_seats/seats\t{}",
+            ThresholdEstimates::HEADER
+        )
+    }
+
+    fn cells(&self) -> String {
+        format!(
+            "{}\t{:.5}\t{}/{}\t{}",
+            self.malicious_stake_fraction,
+            self.malicious_stake_ratio,
+            self.malicious_seats,
+            self.seats,
+            self.estimates.cells()
+        )
+    }
+}
+
+/// The largest fraction of malicious stake which is tolerated with respect to a threshold, see
+/// [`ToleranceConfig::max_block_corruption`].
+#[derive(PartialEq, Debug)]
+pub struct ToleratedFraction {
+    pub max_malicious_stake_per_shard: Ratio<u128>,
+    /// `None` if not even the smallest simulated fraction is tolerated.
+    pub malicious_stake_fraction: Option<Ratio<u128>>,
+}
+
+/// Simulates every fraction of malicious stake in `config`, choosing malicious validators by
+/// `config.strategy`, and prints the probabilities of corruption as a function of the fraction
+/// along with the largest tolerated fraction per threshold.
+pub fn tolerance(config: &ToleranceConfig) -> anyhow::Result<()> {
+    let raw_validator_data = read_validator_data(config.validator_data.as_path())?;
+    let seed = seed_or_random(config.args.seed);
+    let rows = simulate_fractions(config, &raw_validator_data, seed)?;
+
+    print_table(&rows);
+    for tolerated in tolerated_fractions(&rows, config.max_block_corruption) {
+        println!(
+            "Tolerated malicious stake fraction for max_malicious_stake_per_shard {}: {}",
+            tolerated.max_malicious_stake_per_shard,
+            tolerated
+                .malicious_stake_fraction
+                .map_or("none".to_owned(), |fraction| fraction.to_string())
+        );
+    }
+    config.args.write_table(&rows)?;
+    log_seed(seed, config.args.threads);
+    Ok(())
+}
+
+/// Returns the rows of the corruption probability curve for `raw_validator_data`, simulating
+/// every fraction of malicious stake with rngs seeded by `seed`.
+pub fn simulate_fractions(
+    config: &ToleranceConfig,
+    raw_validator_data: &[RawValidatorData],
+    seed: u64,
+) -> anyhow::Result<Vec<ToleranceRow>> {
+    let run_config = config.args.run_config(
+        config.num_blocks,
+        config.num_shards,
+        config.seats_per_shard,
+        config.stake_per_seat,
+        config.max_malicious_stake_per_shard.clone(),
+    );
+
+    let mut rows = vec![];
+    for &fraction in config.malicious_stake_fractions.0.iter() {
+        if fraction > Ratio::new(1, 1) {
+            anyhow::bail!("malicious stake fraction {fraction} exceeds 1");
+        }
+        let mut raw_validator_data = raw_validator_data.to_vec();
+        mark_stake_fraction(&mut raw_validator_data, config.strategy, fraction, seed)?;
+        let (run_config, population_stats, population) =
+            prepare_population(&run_config, &raw_validator_data)?;
+        let counters = simulate_run_config(&run_config, &population, seed)?;
+        let malicious_stake_ratio =
+            Ratio::new(population_stats.malicious_stake, population_stats.stake)
+                .to_f64()
+                .unwrap();
+        println!(
+            "Simulated malicious stake fraction {fraction} (actual ≈ {malicious_stake_ratio:.5})"
+        );
+
+        rows.extend(
+            ThresholdEstimates::from_counters(&counters)
+                .into_iter()
+                .map(|estimates| ToleranceRow {
+                    malicious_stake_fraction: fraction,
+                    malicious_stake_ratio,
+                    malicious_seats: population_stats.malicious_seats,
+                    seats: population_stats.seats,
+                    estimates,
+                }),
+        );
+    }
+    Ok(rows)
+}
+
+/// Returns the largest tolerated fraction of malicious stake for every threshold in `rows`, in
+/// the order in which thresholds occur.
+///
+/// A fraction is tolerated if the estimated probability that any shard in a block is corrupted
+/// does not exceed `max_block_corruption` for it and all smaller simulated fractions.
+pub fn tolerated_fractions(
+    rows: &[ToleranceRow],
+    max_block_corruption: f64,
+) -> Vec<ToleratedFraction> {
+    let mut tolerated: Vec<ToleratedFraction> = vec![];
+    for threshold in rows
+        .iter()
+        .map(|row| row.estimates.max_malicious_stake_per_shard)
+    {
+        if tolerated
+            .iter()
+            .any(|t| t.max_malicious_stake_per_shard == threshold)
+        {
+            continue;
+        }
+        let mut threshold_rows: Vec<&ToleranceRow> = rows
+            .iter()
+            .filter(|row| row.estimates.max_malicious_stake_per_shard == threshold)
+            .collect();
+        threshold_rows.sort_by_key(|row| row.malicious_stake_fraction);
+        tolerated.push(ToleratedFraction {
+            max_malicious_stake_per_shard: threshold,
+            malicious_stake_fraction: threshold_rows
+                .iter()
+                .take_while(|row| row.estimates.block_corruption.estimate <= max_block_corruption)
+                .last()
+                .map(|row| row.malicious_stake_fraction),
+        });
+    }
+    tolerated
+}
+
+#[cfg(test)]
+mod tests {
+    use num_rational::Ratio;
+    use std::path::PathBuf;
+
+    use super::{simulate_fractions, tolerated_fractions, ToleranceConfig, ToleratedFraction};
+    use crate::malicious::StakeStrategy;
+    use crate::seat_price::StakePerSeat;
+    use crate::sweep::{SimulationArgs, ValueList};
+    use crate::validator::tests::new_test_raw_validator_data;
+
+    fn new_test_config() -> ToleranceConfig {
+        // The test validators cover 17 seats with a stake of 1_800.
+        ToleranceConfig {
+            num_blocks: 2_000,
+            num_shards: 2,
+            seats_per_shard: 4,
+            stake_per_seat: StakePerSeat::Fixed(100),
+            malicious_stake_fractions: "1/20,1/10,1/5,1/2,1".parse::<ValueList<_>>().unwrap(),
+            strategy: StakeStrategy::Smallest,
+            max_block_corruption: 0.0,
+            max_malicious_stake_per_shard: vec![Ratio::new(1, 2)],
+            validator_data: PathBuf::new(),
+            args: SimulationArgs::default_for_test(),
+        }
+    }
+
+    #[test]
+    fn test_corruption_rises_with_malicious_stake() {
+        let rows =
+            simulate_fractions(&new_test_config(), &new_test_raw_validator_data(), 42).unwrap();
+        let estimates: Vec<f64> = rows
+            .iter()
+            .map(|row| row.estimates.block_corruption.estimate)
+            .collect();
+        assert_eq!(estimates.len(), 5);
+        assert!(
+            estimates.windows(2).all(|pair| pair[0] <= pair[1]),
+            "{estimates:?}"
+        );
+        assert_eq!(estimates[0], 0.0);
+        assert_eq!(estimates[4], 1.0);
+    }
+
+    #[test]
+    fn test_tolerated_fractions() {
+        let rows =
+            simulate_fractions(&new_test_config(), &new_test_raw_validator_data(), 42).unwrap();
+        // Up to 1/10 the smallest validators hold at most one seat, which cannot corrupt a shard
+        // of 4 seats with threshold 1/2. At 1/5 they hold 3 seats.
+        assert_eq!(
+            tolerated_fractions(&rows, 0.0),
+            vec![ToleratedFraction {
+                max_malicious_stake_per_shard: Ratio::new(1, 2),
+                malicious_stake_fraction: Some(Ratio::new(1, 10)),
+            }]
+        );
+        assert_eq!(
+            tolerated_fractions(&rows, 1.0)[0].malicious_stake_fraction,
+            Some(Ratio::new(1, 1))
+        );
+    }
+}
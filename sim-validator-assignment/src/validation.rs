@@ -0,0 +1,442 @@
+use clap::Args;
+use num_rational::Ratio;
+use num_traits::{One, Zero};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::analyze::AnalyzeConfig;
+use crate::config::Config;
+use crate::seat_price::StakePerSeat;
+use crate::seat_stats::SeatStatsConfig;
+use crate::validator::{read_unchecked_validator_data, RawValidatorData};
+
+#[derive(Args, Debug)]
+pub struct ValidateDataConfig {
+    /// The file from which validator data is read. It is expected to contain a vector of
+    /// `RawValidatorData` serialized as JSON.
+    #[arg(long)]
+    pub validator_data: PathBuf,
+}
+
+/// Prints a [`ValidatorDataReport`] for the validator data in `config` and returns an error if the
+/// data is invalid.
+pub fn validate_data(config: &ValidateDataConfig) -> anyhow::Result<()> {
+    let raw_validator_data = read_unchecked_validator_data(config.validator_data.as_path())?;
+    let report = ValidatorDataReport::new(&raw_validator_data);
+    report.print();
+    report.ensure_valid()
+}
+
+/// Summarizes a set of validators and the problems found in their data.
+#[derive(Serialize, PartialEq, Debug)]
+pub struct ValidatorDataReport {
+    pub num_validators: usize,
+    pub num_malicious_validators: usize,
+    /// The sum of validator stakes, `None` if it overflows.
+    pub stake: Option<u128>,
+    /// The sum of malicious validator stakes, `None` if it overflows.
+    pub malicious_stake: Option<u128>,
+    /// The account ids which occur more than once, in the order of their first occurrence.
+    pub duplicate_account_ids: Vec<String>,
+    /// The account ids of validators with zero stake, in the order of occurrence.
+    pub zero_stake_account_ids: Vec<String>,
+}
+
+impl ValidatorDataReport {
+    pub fn new(raw_validator_data: &[RawValidatorData]) -> Self {
+        let mut occurrences = HashMap::new();
+        let mut duplicate_account_ids = vec![];
+        for v in raw_validator_data.iter() {
+            let count = occurrences.entry(v.account_id.as_str()).or_insert(0);
+            *count += 1;
+            if *count == 2 {
+                duplicate_account_ids.push(v.account_id.clone());
+            }
+        }
+
+        let malicious = raw_validator_data.iter().filter(|v| v.is_malicious);
+        Self {
+            num_validators: raw_validator_data.len(),
+            num_malicious_validators: malicious.clone().count(),
+            stake: checked_sum(raw_validator_data.iter().map(|v| v.stake)),
+            malicious_stake: checked_sum(malicious.map(|v| v.stake)),
+            duplicate_account_ids,
+            zero_stake_account_ids: raw_validator_data
+                .iter()
+                .filter(|v| v.stake == 0)
+                .map(|v| v.account_id.clone())
+                .collect(),
+        }
+    }
+
+    /// Returns an error if the data contains duplicate account ids or its sum of stakes
+    /// overflows. Validators with zero stake are not considered an error.
+    pub fn ensure_valid(&self) -> anyhow::Result<()> {
+        if !self.duplicate_account_ids.is_empty() {
+            anyhow::bail!(
+                "validator data contains duplicate account ids: {}",
+                self.duplicate_account_ids.join(", ")
+            );
+        }
+        if self.stake.is_none() {
+            anyhow::bail!("the sum of validator stakes overflows u128");
+        }
+        Ok(())
+    }
+
+    pub fn print(&self) {
+        let format_stake = |stake: Option<u128>| match stake {
+            Some(stake) => stake.to_string(),
+            None => "overflow".to_owned(),
+        };
+        println!("num_validators\t{}", self.num_validators);
+        println!(
+            "num_malicious_validators\t{}",
+            self.num_malicious_validators
+        );
+        println!("stake\t{}", format_stake(self.stake));
+        println!("malicious_stake\t{}", format_stake(self.malicious_stake));
+        println!(
+            "num_duplicate_account_ids\t{}",
+            self.duplicate_account_ids.len()
+        );
+        for account_id in self.duplicate_account_ids.iter() {
+            println!("duplicate_account_id\t{account_id}");
+        }
+        println!("num_zero_stake\t{}", self.zero_stake_account_ids.len());
+        for account_id in self.zero_stake_account_ids.iter() {
+            println!("zero_stake_account_id\t{account_id}");
+        }
+    }
+}
+
+fn checked_sum(mut stakes: impl Iterator<Item = u128>) -> Option<u128> {
+    stakes.try_fold(0u128, |sum, stake| sum.checked_add(stake))
+}
+
+/// Checks that `config` describes a simulation which can be run with `raw_validator_data`.
+///
+/// It is meant to be called before simulating, to report bad input with a descriptive error
+/// instead of panicking deep in the simulation.
+pub fn validate_config(
+    config: &Config,
+    raw_validator_data: &[RawValidatorData],
+) -> anyhow::Result<()> {
+    validate_validator_data(raw_validator_data)?;
+    validate_stake_per_seat(config.stake_per_seat)?;
+    if config.adversary_budget.is_some() && config.stake_per_seat == StakePerSeat::Auto {
+        anyhow::bail!("adversary_budget requires a fixed stake_per_seat to split the budget by");
+    }
+
+    validate_shards(config.num_shards, config.seats_per_shard)?;
+    if config.block_limit() == 0 {
+        anyhow::bail!("at least one block is required to run the simulation");
+    }
+    if config.threads == 0 {
+        anyhow::bail!("at least one thread is required to run the simulation");
+    }
+
+    validate_thresholds(&config.max_malicious_stake_per_shard)?;
+    validate_ratio("liveness_threshold", config.liveness_threshold)?;
+    validate_ratio("safety_threshold", config.safety_threshold)?;
+    if config.liveness_threshold > config.safety_threshold {
+        anyhow::bail!(
+            "liveness threshold {} exceeds safety threshold {}",
+            config.liveness_threshold,
+            config.safety_threshold
+        );
+    }
+
+    if config.histogram_buckets == Some(0) {
+        anyhow::bail!("the histogram of the malicious ratio requires at least one bucket");
+    }
+
+    Ok(())
+}
+
+/// Checks that stats of seats can be computed for `raw_validator_data` according to `config`.
+pub fn validate_seat_stats_config(
+    config: &SeatStatsConfig,
+    raw_validator_data: &[RawValidatorData],
+) -> anyhow::Result<()> {
+    validate_validator_data(raw_validator_data)?;
+    validate_stake_per_seat(config.stake_per_seat)?;
+
+    match config.stake_per_seat {
+        StakePerSeat::Auto => {
+            if config.num_seats == Some(0) {
+                anyhow::bail!("num_seats must be greater than zero");
+            }
+        }
+        StakePerSeat::Fixed(stake_per_seat) => {
+            if raw_validator_data.iter().all(|v| v.stake < stake_per_seat) {
+                anyhow::bail!(
+                    "no validator has enough stake for a seat with stake_per_seat {stake_per_seat}"
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that the probabilities of corruption can be computed for `raw_validator_data`
+/// according to `config`.
+pub fn validate_analyze_config(
+    config: &AnalyzeConfig,
+    raw_validator_data: &[RawValidatorData],
+) -> anyhow::Result<()> {
+    validate_validator_data(raw_validator_data)?;
+    validate_stake_per_seat(StakePerSeat::Fixed(config.stake_per_seat))?;
+    validate_shards(config.num_shards, config.seats_per_shard)?;
+    validate_thresholds(&config.max_malicious_stake_per_shard)
+}
+
+/// Checks that there is at least one validator and that validators hold some stake.
+fn validate_validator_data(raw_validator_data: &[RawValidatorData]) -> anyhow::Result<()> {
+    if raw_validator_data.is_empty() {
+        anyhow::bail!("validator data is empty");
+    }
+    if raw_validator_data.iter().all(|v| v.stake == 0) {
+        anyhow::bail!("validators hold no stake");
+    }
+    Ok(())
+}
+
+fn validate_stake_per_seat(stake_per_seat: StakePerSeat) -> anyhow::Result<()> {
+    if stake_per_seat == StakePerSeat::Fixed(0) {
+        anyhow::bail!("stake_per_seat must be greater than zero");
+    }
+    Ok(())
+}
+
+/// Checks that there is at least one seat and that the number of seats fits into `u64`.
+fn validate_shards(num_shards: u16, seats_per_shard: u64) -> anyhow::Result<()> {
+    if num_shards == 0 {
+        anyhow::bail!("num_shards must be greater than zero");
+    }
+    if seats_per_shard == 0 {
+        anyhow::bail!("seats_per_shard must be greater than zero");
+    }
+    if u64::from(num_shards).checked_mul(seats_per_shard).is_none() {
+        anyhow::bail!(
+            "num_shards {num_shards} * seats_per_shard {seats_per_shard} exceeds the maximum number of seats {}",
+            u64::MAX
+        );
+    }
+    Ok(())
+}
+
+fn validate_thresholds(max_malicious_stake_per_shard: &[Ratio<u128>]) -> anyhow::Result<()> {
+    if max_malicious_stake_per_shard.is_empty() {
+        anyhow::bail!("at least one max_malicious_stake_per_shard is required");
+    }
+    for &threshold in max_malicious_stake_per_shard.iter() {
+        validate_ratio("max_malicious_stake_per_shard", threshold)?;
+    }
+    Ok(())
+}
+
+/// Checks that `ratio` is in `(0, 1]`.
+fn validate_ratio(name: &str, ratio: Ratio<u128>) -> anyhow::Result<()> {
+    if ratio.is_zero() || ratio > Ratio::one() {
+        anyhow::bail!("{name} must be in (0, 1], got {ratio}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use num_rational::Ratio;
+    use std::path::PathBuf;
+
+    use super::{
+        validate_analyze_config, validate_config, validate_seat_stats_config, ValidatorDataReport,
+    };
+    use crate::analyze::AnalyzeConfig;
+    use crate::config::Config;
+    use crate::seat_price::StakePerSeat;
+    use crate::seat_stats::SeatStatsConfig;
+    use crate::validator::tests::new_test_raw_validator_data;
+    use crate::validator::RawValidatorData;
+
+    fn assert_invalid(config: &Config, raw_validator_data: &[RawValidatorData], message: &str) {
+        let err = validate_config(config, raw_validator_data).unwrap_err();
+        assert!(
+            err.to_string().contains(message),
+            "expected error containing {message:?}, got {err:?}"
+        );
+    }
+
+    #[test]
+    fn test_validate_config() {
+        let raw_validator_data = new_test_raw_validator_data();
+        let config = Config::new_mock(true);
+        validate_config(&config, &raw_validator_data).unwrap();
+
+        assert_invalid(&config, &[], "validator data is empty");
+        let no_stake = [RawValidatorData {
+            account_id: "validator_0".to_owned(),
+            stake: 0,
+            is_malicious: false,
+        }];
+        assert_invalid(&config, &no_stake, "validators hold no stake");
+
+        let invalid_configs = [
+            (
+                Config {
+                    stake_per_seat: StakePerSeat::Fixed(0),
+                    ..config.clone()
+                },
+                "stake_per_seat",
+            ),
+            (
+                Config {
+                    stake_per_seat: StakePerSeat::Auto,
+                    adversary_budget: Some(1_000),
+                    ..config.clone()
+                },
+                "adversary_budget",
+            ),
+            (
+                Config {
+                    num_shards: 0,
+                    ..config.clone()
+                },
+                "num_shards",
+            ),
+            (
+                Config {
+                    seats_per_shard: 0,
+                    ..config.clone()
+                },
+                "seats_per_shard",
+            ),
+            (
+                Config {
+                    threads: 0,
+                    ..config.clone()
+                },
+                "thread",
+            ),
+            (
+                Config {
+                    num_blocks: Some(0),
+                    ..config.clone()
+                },
+                "block",
+            ),
+            (
+                Config {
+                    max_malicious_stake_per_shard: vec![Ratio::new(1, 3), Ratio::new(0, 1)],
+                    ..config.clone()
+                },
+                "max_malicious_stake_per_shard must be in (0, 1]",
+            ),
+            (
+                Config {
+                    max_malicious_stake_per_shard: vec![],
+                    ..config.clone()
+                },
+                "at least one max_malicious_stake_per_shard",
+            ),
+            (
+                Config {
+                    safety_threshold: Ratio::new(4, 3),
+                    ..config.clone()
+                },
+                "safety_threshold must be in (0, 1]",
+            ),
+            (
+                Config {
+                    liveness_threshold: Ratio::new(3, 4),
+                    ..config.clone()
+                },
+                "exceeds safety threshold",
+            ),
+        ];
+        for (invalid_config, message) in invalid_configs.iter() {
+            assert_invalid(invalid_config, &raw_validator_data, message);
+        }
+    }
+
+    #[test]
+    fn test_validate_seat_stats_config() {
+        let raw_validator_data = new_test_raw_validator_data();
+        let new_config = |stake_per_seat, num_seats| SeatStatsConfig {
+            stake_per_seat,
+            num_seats,
+            validator_data: PathBuf::new(),
+            include_partial_seats: false,
+        };
+
+        validate_seat_stats_config(
+            &new_config(StakePerSeat::Fixed(100), None),
+            &raw_validator_data,
+        )
+        .unwrap();
+        validate_seat_stats_config(
+            &new_config(StakePerSeat::Auto, Some(5)),
+            &raw_validator_data,
+        )
+        .unwrap();
+        assert!(validate_seat_stats_config(
+            &new_config(StakePerSeat::Fixed(0), None),
+            &raw_validator_data
+        )
+        .is_err());
+        assert!(validate_seat_stats_config(
+            &new_config(StakePerSeat::Fixed(1_000), None),
+            &raw_validator_data
+        )
+        .is_err());
+        assert!(validate_seat_stats_config(
+            &new_config(StakePerSeat::Auto, Some(0)),
+            &raw_validator_data
+        )
+        .is_err());
+        assert!(
+            validate_seat_stats_config(&new_config(StakePerSeat::Fixed(100), None), &[]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_validate_analyze_config() {
+        let raw_validator_data = new_test_raw_validator_data();
+        let new_config = |num_shards, stake_per_seat| AnalyzeConfig {
+            num_shards,
+            seats_per_shard: 2,
+            stake_per_seat,
+            max_malicious_stake_per_shard: vec![Ratio::new(1, 3)],
+            validator_data: None,
+        };
+
+        validate_analyze_config(&new_config(4, 100), &raw_validator_data).unwrap();
+        assert!(validate_analyze_config(&new_config(4, 0), &raw_validator_data).is_err());
+        assert!(validate_analyze_config(&new_config(0, 100), &raw_validator_data).is_err());
+        assert!(validate_analyze_config(&new_config(4, 100), &[]).is_err());
+    }
+
+    #[test]
+    fn test_validator_data_report() {
+        let report = ValidatorDataReport::new(&new_test_raw_validator_data());
+        insta::assert_yaml_snapshot!(report);
+        report.ensure_valid().unwrap();
+
+        let new_raw = |account_id: &str, stake, is_malicious| RawValidatorData {
+            account_id: account_id.to_owned(),
+            stake,
+            is_malicious,
+        };
+        let report = ValidatorDataReport::new(&[
+            new_raw("a", u128::MAX, true),
+            new_raw("b", 0, false),
+            new_raw("a", 1, true),
+            new_raw("c", 0, false),
+            new_raw("a", 1, false),
+        ]);
+        insta::assert_yaml_snapshot!(report);
+        assert!(report.ensure_valid().is_err());
+    }
+}
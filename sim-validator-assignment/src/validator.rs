@@ -6,8 +6,9 @@ use std::path::Path;
 use crate::config::seats_per_stake;
 use crate::partial_seat::PartialSeat;
 use crate::seat::Seat;
+use crate::validation::ValidatorDataReport;
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct RawValidatorData {
     pub account_id: String,
     pub stake: u128,
@@ -16,26 +17,41 @@ pub struct RawValidatorData {
 
 /// Reads validator data from a file exptected to contain `Vec<RawValidatorData>` serialized as
 /// JSON.
-pub(crate) fn read_validator_data(file_path: &Path) -> anyhow::Result<Vec<RawValidatorData>> {
+///
+/// Data with duplicate account ids or a sum of stakes that overflows results in an error, while
+/// validators with zero stake are reported as a warning. See [`ValidatorDataReport`].
+pub fn read_validator_data(file_path: &Path) -> anyhow::Result<Vec<RawValidatorData>> {
+    let raw_validator_data = read_unchecked_validator_data(file_path)?;
+    let report = ValidatorDataReport::new(&raw_validator_data);
+    report.ensure_valid()?;
+    if !report.zero_stake_account_ids.is_empty() {
+        eprintln!(
+            "warning: {} validators have zero stake",
+            report.zero_stake_account_ids.len()
+        );
+    }
+    Ok(raw_validator_data)
+}
+
+/// Like [`read_validator_data`], but without checking the data.
+pub fn read_unchecked_validator_data(file_path: &Path) -> anyhow::Result<Vec<RawValidatorData>> {
     let file_content = read_to_string(file_path)?;
     serde_json::from_str::<Vec<RawValidatorData>>(&file_content).map_err(|err| err.into())
 }
 
+/// Returns stats of the validators in `input` along with their representation as [`Validator`].
+///
+/// An error is returned if the sum of stakes or seats overflows.
 pub fn parse_raw_validator_data(
     input: &[RawValidatorData],
     stake_per_seat: u128,
-) -> (PopulationStats, Vec<Validator>) {
+) -> anyhow::Result<(PopulationStats, Vec<Validator>)> {
     let mut population_stats = PopulationStats::default();
     let mut validators = vec![];
 
     for v in input.iter() {
-        population_stats.stake += v.stake;
         let num_seats = seats_per_stake(v.stake, stake_per_seat);
-        population_stats.seats += num_seats;
-        if v.is_malicious {
-            population_stats.malicious_stake += v.stake;
-            population_stats.malicious_seats += num_seats;
-        }
+        population_stats.add(v, num_seats)?;
     }
 
     for v in input.iter() {
@@ -48,7 +64,7 @@ pub fn parse_raw_validator_data(
         })
     }
 
-    (population_stats, validators)
+    Ok((population_stats, validators))
 }
 
 impl From<dl_validator_data::ValidatorData> for RawValidatorData {
@@ -77,6 +93,38 @@ pub struct PopulationStats {
     pub malicious_seats: u64,
 }
 
+impl PopulationStats {
+    /// Adds the stake and `seats` of `validator` to the totals, and to the malicious totals if the
+    /// validator is malicious.
+    fn add(&mut self, validator: &RawValidatorData, seats: u64) -> anyhow::Result<()> {
+        add_checked(&mut self.stake, &mut self.seats, validator.stake, seats)?;
+        if validator.is_malicious {
+            add_checked(
+                &mut self.malicious_stake,
+                &mut self.malicious_seats,
+                validator.stake,
+                seats,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn add_checked(
+    total_stake: &mut u128,
+    total_seats: &mut u64,
+    stake: u128,
+    seats: u64,
+) -> anyhow::Result<()> {
+    *total_stake = total_stake
+        .checked_add(stake)
+        .ok_or_else(|| anyhow::anyhow!("the sum of validator stakes overflows u128"))?;
+    *total_seats = total_seats
+        .checked_add(seats)
+        .ok_or_else(|| anyhow::anyhow!("the sum of validator seats overflows u64"))?;
+    Ok(())
+}
+
 #[derive(Serialize, PartialEq, Debug)]
 pub struct Validator {
     account_id: String,
@@ -89,7 +137,7 @@ pub struct Validator {
 impl Validator {
     /// Returns the validator's seats. The number of seats a validator claims is determined by the
     /// stake required per seat and the validator's stake.
-    pub fn seats(&self) -> Vec<Seat> {
+    pub fn seats(&self) -> Vec<Seat<'_>> {
         let seat = Seat::new(self);
         vec![seat.clone(); self.num_seats_as_usize()]
     }
@@ -107,21 +155,27 @@ impl Validator {
     /// # Panics
     ///
     /// Panics if `stake_per_seat` is 0.
-    pub fn partial_seat(&self, stake_per_seat: u128) -> PartialSeat {
+    pub fn partial_seat(&self, stake_per_seat: u128) -> PartialSeat<'_> {
         let weight = self.stake % stake_per_seat;
         PartialSeat::new(self, weight)
     }
 
+    pub fn get_account_id(&self) -> &str {
+        &self.account_id
+    }
+
     pub fn get_is_malicious(&self) -> bool {
         self.is_malicious
     }
 }
 
-pub fn new_ordered_seats(validators: &[Validator]) -> Vec<Seat> {
-    // The `i`th element holds the seats of `validators[i]`
-    let seats_per_validator: Vec<Vec<Seat>> = validators.iter().map(|v| v.seats()).collect();
-
-    seats_per_validator.into_iter().flatten().collect()
+/// Constructs the seats of all `validators`, with the seats of `validators[i]` preceding those of
+/// `validators[i + 1]`.
+pub fn new_ordered_seats(validators: &[Validator]) -> Vec<Seat<'_>> {
+    validators
+        .iter()
+        .flat_map(|v| std::iter::repeat_n(Seat::new(v), v.num_seats_as_usize()))
+        .collect()
 }
 
 /// Constructs `PartialSeats` for the provided `validators`. A validator whose partial seat has a
@@ -129,7 +183,7 @@ pub fn new_ordered_seats(validators: &[Validator]) -> Vec<Seat> {
 pub fn new_ordered_partial_seats(
     validators: &[Validator],
     stake_per_seat: u128,
-) -> Vec<PartialSeat> {
+) -> Vec<PartialSeat<'_>> {
     validators
         .iter()
         .filter_map(|v| {
@@ -240,7 +294,8 @@ pub mod tests {
 
         let config = Config::new_mock(false);
         let (_, validators) =
-            parse_raw_validator_data(&new_test_raw_validator_data(), config.stake_per_seat);
+            parse_raw_validator_data(&new_test_raw_validator_data(), config.stake_per_seat.get())
+                .unwrap();
         // Use a small set of validators to avoid bloating snapshot files.
         let validators = &validators[0..3];
         insta::with_settings!({
@@ -258,7 +313,8 @@ pub mod tests {
     fn test_parse_raw_validator_input() {
         let config = Config::new_mock(false);
         let (population_stats, validators) =
-            parse_raw_validator_data(&new_test_raw_validator_data(), config.stake_per_seat);
+            parse_raw_validator_data(&new_test_raw_validator_data(), config.stake_per_seat.get())
+                .unwrap();
 
         insta::with_settings!({
             info => &config,
@@ -295,7 +351,8 @@ pub mod tests {
 
         let config = Config::new_mock(true);
         let (_, validators) =
-            parse_raw_validator_data(&new_test_raw_validator_data(), config.stake_per_seat);
+            parse_raw_validator_data(&new_test_raw_validator_data(), config.stake_per_seat.get())
+                .unwrap();
         insta::with_settings!({
             info => &(
                 &config,
@@ -303,7 +360,7 @@ pub mod tests {
                 &validators
             ),
         }, {
-            insta::assert_yaml_snapshot!(new_ordered_partial_seats(&validators, config.stake_per_seat));
+            insta::assert_yaml_snapshot!(new_ordered_partial_seats(&validators, config.stake_per_seat.get()));
         })
     }
 }